@@ -0,0 +1,89 @@
+/*
+  Error of anything the user started - a command from the frontend, a tray click or a shortcut.
+  Commands hand it to the frontend as `{ kind, message }`. Background work(tray clicks, shortcuts) shows it as a notification instead,
+  so that a desk being away never takes the whole tray app down.
+*/
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use tauri::AppHandle;
+use thiserror::Error;
+
+use crate::{
+    config_utils::ConfigError, loose_idasen::BtError, preset_transfer::PresetError, tray_utils,
+};
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("Desk is not connected.")]
+    DeskNotConnected,
+
+    #[error("{0}")]
+    Bluetooth(BtError),
+
+    #[error("{0}")]
+    Config(#[from] ConfigError),
+
+    #[error("{0}")]
+    Presets(#[from] PresetError),
+
+    /// Something the user picked(a position, a group) is not in the config(anymore)
+    #[error("{0}")]
+    NotFound(String),
+
+    /// Something the user typed in does not make sense, eg. a height the desk cannot reach
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+impl AppError {
+    /// Lets the frontend tell errors apart without parsing the message
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::DeskNotConnected => "desk_not_connected",
+            AppError::Bluetooth(_) => "bluetooth",
+            AppError::Config(_) => "config",
+            AppError::Presets(_) => "presets",
+            AppError::NotFound(_) => "not_found",
+            AppError::InvalidInput(_) => "invalid_input",
+        }
+    }
+}
+
+impl From<BtError> for AppError {
+    fn from(error: BtError) -> Self {
+        match error {
+            // Nothing to move or read before the desk gets connected
+            BtError::NotInitiated => AppError::DeskNotConnected,
+            error => AppError::Bluetooth(error),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut app_error = serializer.serialize_struct("AppError", 2)?;
+        app_error.serialize_field("kind", self.kind())?;
+        app_error.serialize_field("message", &self.to_string())?;
+        app_error.end()
+    }
+}
+
+/// Tells the user about an error of something running in the background, eg. a move started from the tray
+pub fn report(app_handle: &AppHandle, context: &str, error: &AppError) {
+    log::error!("{}: {}", context, error);
+    tray_utils::show_notification(app_handle, &format!("{}: {}", context, error));
+}
+
+#[cfg(test)]
+mod app_error_suite {
+    use crate::{app_error::AppError, loose_idasen::BtError};
+
+    #[test]
+    fn should_serialize_kind_and_message() {
+        let error = AppError::from(BtError::NotInitiated);
+
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "kind": "desk_not_connected", "message": "Desk is not connected." })
+        );
+    }
+}
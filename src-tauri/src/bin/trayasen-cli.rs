@@ -0,0 +1,497 @@
+/*
+  Command line interface to control the desk without the tray - handy for shell scripts and cron jobs.
+  It shares the desk logic and the config file with the tray app, so presets added here show up in the tray too.
+  When the tray app is running, desk commands go through its local API, since the app owns the bluetooth connection.
+*/
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use trayasen::{
+    config_utils::{self, ConfigData, Position},
+    height::RawHeight,
+    local_api::{self, ApiCommand},
+    loose_idasen::{self, BtError, PositionSpeed},
+    preset_transfer::{self, ImportMode},
+};
+
+#[derive(Parser)]
+#[command(
+    name = "trayasen-cli",
+    version,
+    about = "Control your IKEA Idasen desk from the command line"
+)]
+struct Cli {
+    /// Print JSON instead of human-readable output
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Print diagnostic logs to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List bluetooth devices which might be desks
+    Scan,
+    /// List bluetooth adapters - one of them can be picked with `adapter` in the config
+    Adapters,
+    /// Connect to the desk with the provided name and make it the desk in use. Other saved desks are kept.
+    Connect { name: String },
+    /// Print the current height of the desk
+    Height,
+    /// Move the desk to a height(in the unit from the config, cm by default) or to a saved preset
+    Move { target: String },
+    /// Move the desk up by the provided amount of cm(or inches)
+    Up {
+        #[arg(default_value_t = 1.0)]
+        amount: f32,
+    },
+    /// Move the desk down by the provided amount of cm(or inches)
+    Down {
+        #[arg(default_value_t = 1.0)]
+        amount: f32,
+    },
+    /// Stop the desk
+    Stop,
+    /// Print the height limits of the desk, or change them. Moves and new presets have to stay within them.
+    Limits {
+        /// Lowest height the desk may go to, in cm(or inches)
+        #[arg(long)]
+        min: Option<f32>,
+        /// Highest height the desk may go to, in cm(or inches)
+        #[arg(long)]
+        max: Option<f32>,
+    },
+    /// Manage saved positions
+    Presets {
+        #[command(subcommand)]
+        command: PresetsCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetsCommand {
+    List,
+    /// Save a new position
+    Add {
+        name: String,
+        /// Height in cm(or inches)
+        height: f32,
+        /// Global shortcut moving the desk to this position, eg. "CmdOrCtrl+Shift+1"
+        #[arg(long)]
+        shortcut: Option<String>,
+    },
+    Remove {
+        name: String,
+    },
+    /// Save all positions to a file, to share them with others. `.toml` files are written as TOML, anything else as JSON.
+    Export {
+        path: PathBuf,
+    },
+    /// Load positions from a file created by `export`
+    Import {
+        path: PathBuf,
+        /// What to do with positions which are already saved: merge, replace or skip-duplicates
+        #[arg(long, default_value = "merge")]
+        mode: ImportMode,
+    },
+}
+
+// Heights are stored as the desk reports them - they are entered and printed in the unit from the config, with the offset of the desk applied
+fn to_raw_height(config: &ConfigData, value: f32) -> Result<RawHeight, String> {
+    config
+        .raw_height_in_limits(value)
+        .map_err(|e| e.to_string())
+}
+
+fn height_json(config: &ConfigData, height: RawHeight) -> serde_json::Value {
+    json!({
+        "height": config.height_in_unit(height),
+        "unit": config.height_unit,
+        "height_raw": height,
+    })
+}
+
+fn print_output(json: bool, human: String, machine: serde_json::Value) {
+    if json {
+        println!("{}", machine);
+    } else {
+        println!("{}", human);
+    }
+}
+
+/// Config shared with the tray app. A broken one gets backed up and replaced, same as in the app.
+fn load_config() -> config_utils::ConfigData {
+    let (config, config_recovery) = config_utils::load_or_recover_config();
+    if let Some(config_recovery) = config_recovery {
+        log::warn!("{}", config_recovery);
+    }
+    config
+}
+
+/// Writes the config, failing the command when it could not be saved
+fn save_config(config: &ConfigData) -> Result<(), String> {
+    config_utils::write_config(config).map_err(|e| e.to_string())
+}
+
+/// Connects to whichever saved desk is nearby, the one in use first. Returns the name of the connected desk too.
+async fn connect_to_saved_desk() -> Result<(String, loose_idasen::ConnectedDesk), String> {
+    let config = load_config();
+    if config.local_name.is_none() {
+        return Err("No desk saved - run `trayasen-cli connect <name>` first.".to_string());
+    }
+    loose_idasen::connect_to_any_desk(&config.saved_desks(), &config.adapter)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Config with the provided saved desk in use, so that heights get its offset, limits and presets.
+/// It's only used for this command and never saved.
+fn config_for_desk(desk_name: &str) -> ConfigData {
+    let mut config = load_config();
+    config.local_name = Some(desk_name.to_string());
+    config
+}
+
+fn height_from_api_data(data: &serde_json::Value) -> Result<RawHeight, String> {
+    data["height"]
+        .as_u64()
+        .map(|height| RawHeight(height as u16))
+        .ok_or("Unexpected response from Trayasen.".to_string())
+}
+
+/// Current height and speed of the desk, with the config of that desk. Goes through the tray app if it's running,
+/// since it owns the connection - it reports the desk it has in use.
+async fn get_position_and_speed() -> Result<(ConfigData, PositionSpeed), String> {
+    if let Some(response) = local_api::send_command(ApiCommand::Height).await {
+        let data = response?;
+        let position_speed = PositionSpeed {
+            position: height_from_api_data(&data)?,
+            speed: data["speed"].as_i64().unwrap_or(0) as i16,
+        };
+        return Ok((load_config(), position_speed));
+    }
+
+    let (desk_name, desk) = connect_to_saved_desk().await?;
+    let position_speed = loose_idasen::get_position_and_speed(&desk)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((config_for_desk(&desk_name), position_speed))
+}
+
+/// Where to move the desk. Worked out only once it's known which desk moves, since heights depend on its offset.
+enum MoveTarget {
+    /// Height in the unit from the config
+    Height(f32),
+    Preset(String),
+    /// Relative to the current height, in the unit from the config
+    By(f32),
+}
+
+impl MoveTarget {
+    fn to_raw_height(
+        &self,
+        config: &ConfigData,
+        current_position: RawHeight,
+    ) -> Result<RawHeight, String> {
+        match self {
+            MoveTarget::Height(height) => to_raw_height(config, *height),
+            MoveTarget::Preset(name) => config
+                .saved_positions()
+                .iter()
+                .find(|pos| &pos.name == name)
+                .map(|pos| pos.value)
+                .ok_or(format!("No saved position named `{}`.", name)),
+            MoveTarget::By(amount) => {
+                to_raw_height(config, config.height_in_unit(current_position) + amount)
+            }
+        }
+    }
+}
+
+/// Moves the saved desk, stopping it on Ctrl+C. Returns where it stopped, with the config of the desk which moved.
+async fn move_saved_desk(target: MoveTarget) -> Result<(ConfigData, RawHeight), String> {
+    if let Some(response) = local_api::send_command(ApiCommand::Height).await {
+        // The tray app moves the desk it has in use
+        let config = load_config();
+        let target_position = target.to_raw_height(&config, height_from_api_data(&response?)?)?;
+        let api_move = local_api::send_command(ApiCommand::MoveTo {
+            height: target_position,
+        });
+        tokio::select! {
+            response = api_move => {
+                if let Some(response) = response {
+                    return Ok((config, height_from_api_data(&response?)?));
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                local_api::send_command(ApiCommand::Stop).await;
+                return get_position_and_speed().await.map(|(config, pos)| (config, pos.position));
+            }
+        }
+    }
+
+    let (desk_name, desk) = connect_to_saved_desk().await?;
+    let config = config_for_desk(&desk_name);
+    let current_position = loose_idasen::get_position(&desk)
+        .await
+        .map_err(|e| e.to_string())?;
+    let target_position = target.to_raw_height(&config, current_position)?;
+    let cancel_token = CancellationToken::new();
+    let ctrl_c_token = cancel_token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            ctrl_c_token.cancel();
+        }
+    });
+
+    let limits = config.desk_height_limits(&desk_name);
+    match loose_idasen::move_to_target(&desk, target_position, limits, &cancel_token).await {
+        Ok(()) | Err(BtError::MovementCancelled) => {}
+        Err(e) => return Err(e.to_string()),
+    }
+    let position = loose_idasen::get_position(&desk)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((config, position))
+}
+
+async fn stop_saved_desk() -> Result<(), String> {
+    if let Some(response) = local_api::send_command(ApiCommand::Stop).await {
+        return response.map(|_| ());
+    }
+
+    let (_, desk) = connect_to_saved_desk().await?;
+    loose_idasen::stop(&desk).await.map_err(|e| e.to_string())
+}
+
+fn print_moved(json: bool, (config, position): (ConfigData, RawHeight)) {
+    print_output(
+        json,
+        format!("Desk stopped at {}", config.format_height(position)),
+        height_json(&config, position),
+    );
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let json = cli.json;
+    match cli.command {
+        Command::Scan => {
+            let scan_result = loose_idasen::scan_adapters(None, &load_config().adapter)
+                .await
+                .map_err(|e| e.to_string())?;
+            // Other adapters might still have found the desk
+            for adapter_error in &scan_result.adapter_errors {
+                eprintln!("Warning: adapter {}", adapter_error);
+            }
+            let names: Vec<String> = scan_result
+                .desks
+                .into_iter()
+                .map(|desk| desk.name)
+                .collect();
+            print_output(json, names.join("\n"), json!(names));
+        }
+        Command::Adapters => {
+            // All of them, so that another one can be picked
+            let adapters = loose_idasen::get_adapters(&None)
+                .await
+                .map_err(|e| e.to_string())?;
+            let names: Vec<String> = adapters.into_iter().map(|(name, _)| name).collect();
+            print_output(json, names.join("\n"), json!(names));
+        }
+        Command::Connect { name } => {
+            let desk = loose_idasen::connect_to_desk_by_name_internal(
+                name.clone(),
+                &load_config().adapter,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            config_utils::save_local_name(
+                name.clone(),
+                Some(desk.peripheral_id.clone()),
+                desk.address(),
+            )
+            .map_err(|e| e.to_string())?;
+            print_output(
+                json,
+                format!("Connected to `{}` and saved it as the default desk", name),
+                json!({ "connected": name }),
+            );
+        }
+        Command::Height => {
+            let (config, position_speed) = get_position_and_speed().await?;
+            let mut height_json = height_json(&config, position_speed.position);
+            height_json["speed"] = json!(position_speed.speed);
+            print_output(
+                json,
+                config.format_height(position_speed.position),
+                height_json,
+            );
+        }
+        Command::Move { target } => {
+            let target = match target.parse::<f32>() {
+                Ok(height) => MoveTarget::Height(height),
+                Err(_) => MoveTarget::Preset(target),
+            };
+            print_moved(json, move_saved_desk(target).await?);
+        }
+        Command::Up { amount } => print_moved(json, move_saved_desk(MoveTarget::By(amount)).await?),
+        Command::Down { amount } => {
+            print_moved(json, move_saved_desk(MoveTarget::By(-amount)).await?)
+        }
+        Command::Stop => {
+            stop_saved_desk().await?;
+            print_output(json, "Desk stopped".to_string(), json!({ "stopped": true }));
+        }
+        Command::Limits { min, max } => run_limits(json, min, max)?,
+        Command::Presets { command } => run_presets(json, command)?,
+    }
+    Ok(())
+}
+
+fn run_limits(json: bool, min: Option<f32>, max: Option<f32>) -> Result<(), String> {
+    let mut config = load_config();
+    if min.is_some() || max.is_some() {
+        // The limit which is not provided stays as it is
+        let limits = config.height_limits();
+        let height_limits = config
+            .height_limits_from_unit(
+                min.unwrap_or(config.height_in_unit(limits.min)),
+                max.unwrap_or(config.height_in_unit(limits.max)),
+            )
+            .map_err(|e| e.to_string())?;
+        config.active_desk_mut().height_limits = height_limits;
+        save_config(&config)?;
+    }
+
+    let limits = config.height_limits();
+    print_output(
+        json,
+        format!(
+            "Height limits: {} to {}",
+            config.format_height(limits.min),
+            config.format_height(limits.max)
+        ),
+        json!({
+            "min": height_json(&config, limits.min),
+            "max": height_json(&config, limits.max),
+        }),
+    );
+    Ok(())
+}
+
+fn run_presets(json: bool, command: PresetsCommand) -> Result<(), String> {
+    let mut config = load_config();
+    match command {
+        PresetsCommand::List => {
+            let human = config
+                .saved_positions()
+                .iter()
+                .map(|pos| match &pos.shortcut {
+                    Some(shortcut) if !shortcut.is_empty() => format!(
+                        "{}: {} ({})",
+                        pos.name,
+                        config.format_height(pos.value),
+                        shortcut
+                    ),
+                    _ => format!("{}: {}", pos.name, config.format_height(pos.value)),
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+            print_output(json, human, json!(config.saved_positions()));
+        }
+        PresetsCommand::Add {
+            name,
+            height,
+            shortcut,
+        } => {
+            if config.saved_positions().iter().any(|pos| pos.name == name) {
+                return Err(format!("A position named `{}` already exists.", name));
+            }
+            let position = Position {
+                name,
+                value: to_raw_height(&config, height)?,
+                shortcut: shortcut.filter(|shortcut| !shortcut.is_empty()),
+            };
+            config.saved_positions_mut().push(position.clone());
+            save_config(&config)?;
+            print_output(
+                json,
+                format!(
+                    "Saved `{}` at {}",
+                    position.name,
+                    config.format_height(position.value)
+                ),
+                json!(position),
+            );
+        }
+        PresetsCommand::Remove { name } => {
+            let saved_positions = config.saved_positions_mut();
+            let positions_before = saved_positions.len();
+            saved_positions.retain(|pos| pos.name != name);
+            if saved_positions.len() == positions_before {
+                return Err(format!("No saved position named `{}`.", name));
+            }
+            save_config(&config)?;
+            print_output(
+                json,
+                format!("Removed `{}`", name),
+                json!({ "removed": name }),
+            );
+        }
+        PresetsCommand::Export { path } => {
+            preset_transfer::export_presets_to_file(config.saved_positions(), &path)
+                .map_err(|e| e.to_string())?;
+            print_output(
+                json,
+                format!(
+                    "Exported {} positions to {}",
+                    config.saved_positions().len(),
+                    path.display()
+                ),
+                json!({ "exported": config.saved_positions().len() }),
+            );
+        }
+        PresetsCommand::Import { path, mode } => {
+            // Whole file gets checked before the config is touched
+            let imported =
+                preset_transfer::read_presets_from_file(&path).map_err(|e| e.to_string())?;
+            preset_transfer::check_limits(&imported, config.height_limits())
+                .map_err(|e| e.to_string())?;
+            let summary =
+                preset_transfer::apply_import(config.saved_positions_mut(), imported, mode);
+            save_config(&config)?;
+            print_output(
+                json,
+                format!(
+                    "Imported positions: {} added, {} updated, {} skipped, {} removed",
+                    summary.added, summary.updated, summary.skipped, summary.removed
+                ),
+                json!(summary),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let log_filter = if cli.verbose {
+        "trayasen=debug"
+    } else {
+        "warn"
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_filter)).init();
+
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
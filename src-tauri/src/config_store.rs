@@ -0,0 +1,98 @@
+/*
+  Config of the running app, kept in memory. Every change goes through here, so that two writers(eg. two windows,
+  or a window and the tray) never lose each other's edits, and the tray, the shortcuts and the windows hear about the change right away.
+*/
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::{
+    config_utils::{self, ConfigData, ConfigError},
+    shortcut_utils, tray_utils,
+};
+
+/// Emitted with the new config every time it changes
+pub const CONFIG_CHANGED_EVENT: &str = "config-changed";
+
+/// Config in use, and a feed of it for the background tasks which have to follow its changes
+pub struct TauriConfigStore(Mutex<ConfigData>, watch::Sender<ConfigData>);
+
+impl TauriConfigStore {
+    pub fn new(config: ConfigData) -> Self {
+        TauriConfigStore(Mutex::new(config.clone()), watch::channel(config).0)
+    }
+}
+
+pub fn get_config(app_handle: &AppHandle) -> ConfigData {
+    let config_store = app_handle.state::<TauriConfigStore>();
+    let config = config_store
+        .0
+        .lock()
+        .expect("Error while unwrapping config store");
+    config.clone()
+}
+
+/// Sees every change of the config, starting with the current one
+pub fn subscribe(app_handle: &AppHandle) -> watch::Receiver<ConfigData> {
+    app_handle.state::<TauriConfigStore>().1.subscribe()
+}
+
+/// Applies the change and saves the config. The lock is held until the file is written, so updates happen one after another.
+/// Nothing changes if the config cannot be saved.
+pub fn update_config<R>(
+    app_handle: &AppHandle,
+    update: impl FnOnce(&mut ConfigData) -> R,
+) -> Result<R, ConfigError> {
+    let (result, old_config, new_config) = {
+        let config_store = app_handle.state::<TauriConfigStore>();
+        let mut config = config_store
+            .0
+            .lock()
+            .expect("Error while unwrapping config store");
+        let mut new_config = config.clone();
+        let result = update(&mut new_config);
+        config_utils::write_config(&new_config)?;
+        let old_config = std::mem::replace(&mut *config, new_config.clone());
+        (result, old_config, new_config)
+    };
+
+    shortcut_utils::update_shortcuts(app_handle, &old_config, &new_config);
+    notify_config_changed(app_handle, &new_config);
+    Ok(result)
+}
+
+/// Replaces the config with the one in the file, eg. after it was edited by hand.
+/// The config stays as it was if the file cannot be loaded.
+pub fn reload_config(app_handle: &AppHandle) -> Result<(), ConfigError> {
+    let (old_config, new_config) = {
+        let config_store = app_handle.state::<TauriConfigStore>();
+        let mut config = config_store
+            .0
+            .lock()
+            .expect("Error while unwrapping config store");
+        // Read under the lock, so that an update cannot be saved in between and then undone with the older file
+        let new_config = config_utils::load_config()?;
+        config_utils::release_kept_config();
+        // Our own writes end up here too
+        if *config == new_config {
+            return Ok(());
+        }
+        let old_config = std::mem::replace(&mut *config, new_config.clone());
+        (old_config, new_config)
+    };
+
+    log::info!("Config reloaded from file");
+    shortcut_utils::update_shortcuts(app_handle, &old_config, &new_config);
+    notify_config_changed(app_handle, &new_config);
+    Ok(())
+}
+
+fn notify_config_changed(app_handle: &AppHandle, config: &ConfigData) {
+    _ = app_handle.emit_all(CONFIG_CHANGED_EVENT, config);
+    app_handle
+        .state::<TauriConfigStore>()
+        .1
+        .send_replace(config.clone());
+    tray_utils::refresh_tray_menu(app_handle, config);
+}
@@ -1,36 +1,425 @@
+use chrono::Local;
 use serde_derive::{Deserialize, Serialize};
-use serde_json::{from_str, to_string};
+use serde_json::{from_str, from_value, to_string, Value};
 use std::{
-    fs::{self, read_to_string, remove_file, OpenOptions},
-    io::Write,
+    fs::{self, read_to_string, remove_file},
+    io::ErrorKind,
+    sync::atomic::{AtomicBool, Ordering},
 };
 use tauri::{
-    api::path::data_dir, CustomMenuItem, GlobalShortcutManager, SystemTrayMenu, SystemTrayMenuItem,
-    SystemTraySubmenu,
+    api::path::data_dir, CustomMenuItem, SystemTrayMenu, SystemTrayMenuItem, SystemTraySubmenu,
+};
+
+use crate::{
+    app_error::AppError,
+    config_store,
+    height::{self, Height, HeightLimits, HeightUnit, RawHeight},
+    loose_idasen::HARDWARE_LIMITS,
 };
 
 static CONFIG_FILE_NAME: &str = "idasen-tray-config.json";
+/// Config gets written here first, then moved over the actual config - so that it's never left half-written
+static CONFIG_TEMP_FILE_NAME: &str = "idasen-tray-config.json.tmp";
+/// Where a config which could not be loaded gets moved to - the time of the move goes in between, so that backups never replace each other
+static CONFIG_BACKUP_FILE_PREFIX: &str = "idasen-tray-config";
+static CONFIG_BACKUP_FILE_SUFFIX: &str = "json.bak";
+
+/// Set when the config file could not be loaded and was left as it is - it's never written over until it loads again
+static CONFIG_KEPT: AtomicBool = AtomicBool::new(false);
+
+/// Version of the config layout. Bump it and add a migration to `CONFIG_MIGRATIONS` whenever the layout changes.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+/// Migration at index N turns a config of version N into version N + 1
+const CONFIG_MIGRATIONS: &[fn(Value) -> Value] = &[migrate_v0_to_v1, migrate_v1_to_v2];
 
 pub const QUIT_ID: &str = "quit";
 pub const ABOUT_ID: &str = "about/options";
 pub const ADD_POSITION_ID: &str = "add_position";
 pub const HEADER_ID: &str = "idasen_controller";
 pub const MANAGE_POSITIONS_ID: &str = "manage_positions";
+pub const STOP_ID: &str = "stop_desk";
+pub const PAUSE_REMINDERS_ID: &str = "pause_reminders";
+/// Followed by the name of the desk to switch to
+pub const SWITCH_DESK_ID_PREFIX: &str = "switch_desk:";
+/// Followed by the name of the group and the name of the position - see `group_position_id`
+pub const GROUP_POSITION_ID_PREFIX: &str = "group_position:";
+/// Height in tenth mm from which the desk counts as standing, unless configured otherwise
+pub const DEFAULT_STAND_THRESHOLD: RawHeight = RawHeight(9500);
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Position {
     pub name: String,
-    pub value: u16,
+    pub value: RawHeight,
     /// String representation of shortcut
     pub shortcut: Option<String>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Error while reading the config file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Config file is not valid: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("Config file comes from a newer version of Trayasen (version {0}).")]
+    UnsupportedVersion(u32),
+    #[error(
+        "Config file could not be loaded, so changes are not saved until it can be loaded again."
+    )]
+    Kept,
+}
+
+/// What happened to a config file which could not be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigRecovery {
+    #[error("Your config could not be loaded, so a new one was created. The old one was saved as {backup_path}. ({error})")]
+    BackedUp {
+        error: ConfigError,
+        backup_path: String,
+    },
+    /// File might still be fine, eg. for a newer version of Trayasen - defaults are used without saving them
+    #[error("Your config could not be loaded, so the default settings are used until the file is fixed. The config file was left as it is. ({0})")]
+    Kept(ConfigError),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ConfigData {
+    /// Layout version of the config - see `CURRENT_CONFIG_VERSION`
+    #[serde(default)]
+    pub version: u32,
+    /// Name of the desk in use - `None` until the setup is done
     pub local_name: Option<String>,
+    /// Every desk connected so far
+    #[serde(default)]
+    pub desks: Vec<DeskProfile>,
+    /// Desks moving together, eg. in a meeting room
+    #[serde(default)]
+    pub groups: Vec<DeskGroup>,
+    /// String representation of shortcut stopping the desk
+    #[serde(default)]
+    pub stop_shortcut: Option<String>,
+    /// Broker to publish the desk to. Only used when built with the `mqtt` feature.
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    /// Sit/stand reminders. Disabled when missing.
+    #[serde(default)]
+    pub reminders: Option<ReminderConfig>,
+    /// Bluetooth adapter to use, eg. `hci1` - see `trayasen-cli adapters`. Every adapter is used when missing.
+    #[serde(default)]
+    pub adapter: Option<String>,
+    /// Unit heights are shown and entered in
+    #[serde(default)]
+    pub height_unit: HeightUnit,
+}
+
+impl Default for ConfigData {
+    fn default() -> Self {
+        ConfigData {
+            version: CURRENT_CONFIG_VERSION,
+            local_name: None,
+            desks: vec![],
+            groups: vec![],
+            stop_shortcut: None,
+            mqtt: None,
+            reminders: None,
+            adapter: None,
+            height_unit: HeightUnit::default(),
+        }
+    }
+}
+
+impl ConfigData {
+    pub fn active_desk(&self) -> Option<&DeskProfile> {
+        let local_name = self.local_name.as_deref().unwrap_or_default();
+        self.desks.iter().find(|desk| desk.name == local_name)
+    }
+
+    /// Profile of the desk in use, created when missing
+    pub fn active_desk_mut(&mut self) -> &mut DeskProfile {
+        let local_name = self.local_name.clone().unwrap_or_default();
+        match self.desks.iter().position(|desk| desk.name == local_name) {
+            Some(index) => &mut self.desks[index],
+            None => {
+                self.desks.push(DeskProfile::new(local_name));
+                self.desks.last_mut().expect("Desk was just added")
+            }
+        }
+    }
+
+    /// Positions of the desk in use
+    pub fn saved_positions(&self) -> &[Position] {
+        self.active_desk()
+            .map(|desk| desk.saved_positions.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn saved_positions_mut(&mut self) -> &mut Vec<Position> {
+        &mut self.active_desk_mut().saved_positions
+    }
+
+    /// Makes the desk the one in use, adding a profile for it when it's new.
+    /// The peripheral id and the address are remembered when known, so the desk is found by them next time.
+    pub fn select_desk(
+        &mut self,
+        name: &str,
+        peripheral_id: Option<String>,
+        address: Option<String>,
+    ) {
+        if !self.desks.iter().any(|desk| desk.name == name) {
+            // Positions saved while no desk was known belong to the first desk connected
+            match self.desks.iter_mut().find(|desk| desk.name.is_empty()) {
+                Some(unnamed_desk) => unnamed_desk.name = name.to_string(),
+                None => self.desks.push(DeskProfile::new(name.to_string())),
+            }
+        }
+        self.local_name = Some(name.to_string());
+        if peripheral_id.is_some() {
+            self.active_desk_mut().peripheral_id = peripheral_id;
+        }
+        if address.is_some() {
+            self.active_desk_mut().address = address;
+        }
+    }
+
+    /// Whether it's the desk in use, with its peripheral id and address already saved
+    pub fn is_desk_in_use(&self, name: &str, peripheral_id: &str, address: Option<&str>) -> bool {
+        self.local_name.as_deref() == Some(name)
+            && self.active_desk().is_some_and(|desk| {
+                desk.peripheral_id.as_deref() == Some(peripheral_id)
+                    && desk.address.as_deref() == address
+            })
+    }
+
+    /// Saved desks, the one in use first
+    pub fn saved_desks(&self) -> Vec<DeskProfile> {
+        let mut desks: Vec<DeskProfile> = self
+            .desks
+            .iter()
+            .filter(|desk| !desk.name.is_empty())
+            .cloned()
+            .collect();
+        if let Some(local_name) = &self.local_name {
+            if let Some(index) = desks.iter().position(|desk| &desk.name == local_name) {
+                let local_desk = desks.remove(index);
+                desks.insert(0, local_desk);
+            }
+        }
+        desks
+    }
+
+    /// Calibration offset of the desk in use, in tenth mm
+    pub fn height_offset(&self) -> i16 {
+        self.active_desk()
+            .map(|desk| desk.height_offset)
+            .unwrap_or_default()
+    }
+
+    /// Height limits of the desk in use
+    pub fn height_limits(&self) -> HeightLimits {
+        self.active_desk()
+            .map(DeskProfile::height_limits)
+            .unwrap_or(HARDWARE_LIMITS)
+    }
+
+    /// Height limits of the saved desk with the provided name
+    pub fn desk_height_limits(&self, desk_name: &str) -> HeightLimits {
+        self.desks
+            .iter()
+            .find(|desk| desk.name == desk_name)
+            .map(DeskProfile::height_limits)
+            .unwrap_or(HARDWARE_LIMITS)
+    }
+
+    /// Height limits for the desk in use from heights in the unit the user picked, to be saved in its profile.
+    /// `None` when they cover the whole range of the desk.
+    pub fn height_limits_from_unit(
+        &self,
+        min: f32,
+        max: f32,
+    ) -> Result<Option<HeightLimits>, AppError> {
+        let limits = match (self.raw_height(min), self.raw_height(max)) {
+            (Some(min), Some(max)) if min < max => HeightLimits { min, max },
+            _ => {
+                return Err(AppError::InvalidInput(
+                    "Minimum height has to be below the maximum height.".to_string(),
+                ))
+            }
+        };
+        // Limits past the range of the desk are brought back to it, eg. a max of 200 cm is the same as no max
+        let limits = limits.within(HARDWARE_LIMITS).ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "Height limits have to be between {} and {}.",
+                self.format_height(HARDWARE_LIMITS.min),
+                self.format_height(HARDWARE_LIMITS.max)
+            ))
+        })?;
+        Ok((limits != HARDWARE_LIMITS).then_some(limits))
+    }
+
+    /// Height of the desk in use as the user sees it, eg. `72.5 cm`
+    pub fn format_height(&self, height: RawHeight) -> String {
+        height
+            .calibrate(self.height_offset())
+            .format(self.height_unit)
+    }
+
+    /// Height of the desk in use in the unit the user picked
+    pub fn height_in_unit(&self, height: RawHeight) -> f32 {
+        height
+            .calibrate(self.height_offset())
+            .in_unit(self.height_unit)
+    }
+
+    /// Raw height of the desk in use for a height in the unit the user picked
+    pub fn raw_height(&self, value: f32) -> Option<RawHeight> {
+        Height::from_unit(value, self.height_unit).to_raw(self.height_offset())
+    }
+
+    /// Same as `raw_height`, but fails when the height is outside the height limits of the desk in use
+    pub fn raw_height_in_limits(&self, value: f32) -> Result<RawHeight, AppError> {
+        let limits = self.height_limits();
+        self.raw_height(value)
+            .filter(|height| limits.contains(*height))
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!(
+                    "Height of {:.1} {} is outside the height limits of the desk - it has to be between {} and {}.",
+                    value,
+                    self.height_unit.symbol(),
+                    self.format_height(limits.min),
+                    self.format_height(limits.max)
+                ))
+            })
+    }
+
+    /// Names of the saved desks, the one in use first
+    pub fn desk_names(&self) -> Vec<String> {
+        self.saved_desks()
+            .into_iter()
+            .map(|desk| desk.name)
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DeskProfile {
+    /// Bluetooth name of the desk - empty for positions saved before any desk was connected
+    pub name: String,
+    #[serde(default)]
     pub saved_positions: Vec<Position>,
+    /// Tenth mm added to the height the desk reports, eg. when it has a thicker tabletop
+    #[serde(default)]
+    pub height_offset: i16,
+    /// Soft limits the desk never gets moved past, eg. because of a shelf below it. Missing when the desk can use its whole range.
+    #[serde(default)]
+    pub height_limits: Option<HeightLimits>,
+    /// Bluetooth peripheral id of the desk, preferred over the name when reconnecting. Missing until connected.
+    #[serde(default)]
+    pub peripheral_id: Option<String>,
+    /// Bluetooth address of the desk. Unlike the peripheral id on Linux, it's the same through every adapter.
+    /// Missing until connected, and on macOS.
+    #[serde(default)]
+    pub address: Option<String>,
 }
 
-fn get_config_path() -> String {
+impl DeskProfile {
+    pub fn new(name: String) -> Self {
+        DeskProfile {
+            name,
+            saved_positions: vec![],
+            height_offset: 0,
+            height_limits: None,
+            peripheral_id: None,
+            address: None,
+        }
+    }
+
+    /// Whether the found desk is this one - by its peripheral id, or by its address when found through another adapter
+    pub fn is_same_desk(&self, peripheral_id: &str, address: Option<&str>) -> bool {
+        // Desks saved before their address was still have it in their id on Linux, eg. `hci0/dev_C2_6D_88_A4_2B_01`
+        let saved_address = self.address.clone().or_else(|| {
+            let (_, address) = self.peripheral_id.as_deref()?.rsplit_once("/dev_")?;
+            Some(address.replace('_', ":"))
+        });
+        self.peripheral_id.as_deref() == Some(peripheral_id)
+            || (address.is_some() && saved_address.as_deref() == address)
+    }
+
+    /// Soft limits of the desk, never wider than what the desk can do
+    pub fn height_limits(&self) -> HeightLimits {
+        self.height_limits
+            .and_then(|limits| limits.within(HARDWARE_LIMITS))
+            .unwrap_or(HARDWARE_LIMITS)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DeskGroup {
+    pub name: String,
+    /// Bluetooth names of the desks in the group
+    pub desks: Vec<String>,
+    /// Positions every desk of the group moves to
+    #[serde(default)]
+    pub saved_positions: Vec<Position>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ReminderConfig {
+    /// Minutes of sitting after which the user gets reminded to stand up
+    #[serde(default = "default_sit_minutes")]
+    pub sit_minutes: u32,
+    /// Minutes of standing after which the user gets reminded to sit down
+    #[serde(default = "default_stand_minutes")]
+    pub stand_minutes: u32,
+    /// Height in tenth mm from which the desk counts as standing
+    #[serde(default = "default_stand_threshold")]
+    pub stand_threshold: RawHeight,
+    /// Name of the saved position to move to when it's time to sit down. Desk stays put when missing.
+    #[serde(default)]
+    pub sit_preset: Option<String>,
+    /// Name of the saved position to move to when it's time to stand up. Desk stays put when missing.
+    #[serde(default)]
+    pub stand_preset: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topics of the desk live under `<base_topic>/<desk name>`
+    #[serde(default = "default_mqtt_base_topic")]
+    pub base_topic: String,
+    /// Prefix Home Assistant listens on for discovery messages
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_sit_minutes() -> u32 {
+    45
+}
+
+fn default_stand_minutes() -> u32 {
+    15
+}
+
+fn default_stand_threshold() -> RawHeight {
+    DEFAULT_STAND_THRESHOLD
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_base_topic() -> String {
+    "trayasen".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+/// Path of a file living in the data directory, next to the config
+pub fn get_data_file_path(file_name: &str) -> String {
     let mut dir = data_dir()
         .expect("Error whiel unwrapping data directory")
         .to_str()
@@ -38,114 +427,203 @@ fn get_config_path() -> String {
         .to_string();
 
     if dir.ends_with("/") {
-        dir.push_str(CONFIG_FILE_NAME);
+        dir.push_str(file_name);
     } else {
-        dir.push_str("/");
-        dir.push_str(CONFIG_FILE_NAME);
+        dir.push('/');
+        dir.push_str(file_name);
     }
 
     dir
 }
 
-// TODO: use get_config here? or merge two funcs together?
-// For FIRST loading
-pub fn get_or_create_config() -> ConfigData {
-    let config_path = get_config_path().trim_end().to_string();
+pub fn get_config_path() -> String {
+    get_data_file_path(CONFIG_FILE_NAME)
+}
 
-    println!("Config path: {:?}", config_path);
+fn get_config_temp_path() -> String {
+    get_data_file_path(CONFIG_TEMP_FILE_NAME)
+}
 
-    let config = match read_to_string(&config_path) {
-        // Config exists
-        Ok(s) => {
-            let config =
-                from_str::<ConfigData>(s.as_str()).expect("Error while parsing config file");
-            config
-        }
-        // Config does not exist. Create a dummy one.
-        // Check for different errors?
-        Err(_) => {
-            let new_config = ConfigData {
-                local_name: None,
-
-                saved_positions: vec![],
-            };
-            let stringified_config = to_string::<ConfigData>(&new_config).unwrap();
-            // Using OpenOptions cause it's the easiest to create a file with.
-            let mut conf_file = OpenOptions::new()
-                .write(true)
-                .read(true)
-                .create(true)
-                .open(&config_path.to_string())
-                .expect("Error while creating a new config");
-
-            conf_file.write_all(&stringified_config.as_bytes()).unwrap();
-
-            new_config
-        }
-    };
+fn get_config_backup_path() -> String {
+    get_data_file_path(&format!(
+        "{}-{}.{}",
+        CONFIG_BACKUP_FILE_PREFIX,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        CONFIG_BACKUP_FILE_SUFFIX
+    ))
+}
 
+/// Configs from before versioning. Shortcuts used to be saved as empty strings when not set.
+fn migrate_v0_to_v1(mut config: Value) -> Value {
+    if let Some(positions) = config
+        .get_mut("saved_positions")
+        .and_then(Value::as_array_mut)
+    {
+        for position in positions {
+            if position.get("shortcut") == Some(&Value::String(String::new())) {
+                position["shortcut"] = Value::Null;
+            }
+        }
+    }
+    config["version"] = Value::from(1);
     config
 }
 
-// Generally this function should never error, cause all the same operations have been done miliseconds before.
-pub fn save_local_name(new_local_name: String) {
-    let config_path = get_config_path().trim_end().to_string();
-    let old_conf_file =
-        read_to_string(&config_path.to_string()).expect("Opening a config to save MAC Address");
-    let mut mut_conf_file =
-        from_str::<ConfigData>(&old_conf_file).expect("Parsing a config to save MAC Address");
+/// Configs from before several desks could be saved. Positions move into the profile of the saved desk.
+fn migrate_v1_to_v2(mut config: Value) -> Value {
+    let saved_positions = config
+        .as_object_mut()
+        .and_then(|config| config.remove("saved_positions"))
+        .unwrap_or_else(|| Value::Array(vec![]));
+    let local_name = config
+        .get("local_name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
 
-    mut_conf_file.local_name = Some(new_local_name.to_string());
+    let has_positions = saved_positions
+        .as_array()
+        .is_some_and(|positions| !positions.is_empty());
+    config["desks"] = if local_name.is_empty() && !has_positions {
+        Value::Array(vec![])
+    } else {
+        serde_json::json!([{ "name": local_name, "saved_positions": saved_positions }])
+    };
+    config["version"] = Value::from(2);
+    config
+}
+
+/// Brings a config of any older version up to `CURRENT_CONFIG_VERSION`
+fn migrate_config(mut config: Value) -> Result<Value, ConfigError> {
+    let version = config.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
 
-    let stringified_new_config = to_string::<ConfigData>(&mut_conf_file).unwrap();
-    fs::write(config_path, stringified_new_config)
-        .expect("Saving a config after parsing a MAC Address");
+    for migration in &CONFIG_MIGRATIONS[version as usize..] {
+        config = migration(config);
+    }
+    Ok(config)
 }
 
-#[tauri::command]
-pub fn remove_position(app_handle: tauri::AppHandle, pos_name: &str) -> ConfigData {
-    let mut shortcut_manager = app_handle.global_shortcut_manager();
-    let mut conf = get_config();
+pub fn parse_config(config: &str) -> Result<ConfigData, ConfigError> {
+    let config = from_str::<Value>(config)?;
+    let config = migrate_config(config)?;
+    Ok(from_value::<ConfigData>(config)?)
+}
 
-    let elem_to_unregister = conf.saved_positions.iter().find(|pos| pos_name == pos.name);
+pub fn load_config() -> Result<ConfigData, ConfigError> {
+    let config = read_to_string(get_config_path())?;
+    parse_config(&config)
+}
 
-    if let Some(elem_to_unregister) = elem_to_unregister {
-        let shortcut = elem_to_unregister.shortcut.clone();
-        if let Some(shortcut) = shortcut {
-            if shortcut != "" {
-                _ = shortcut_manager.unregister(shortcut.as_str());
+/// Loads the config, creating it when it does not exist yet.
+/// A config which cannot be parsed gets backed up and replaced with a fresh one. One which cannot be read, or comes from a newer version,
+/// is left alone and defaults are used in the meantime. Either way, what happened is returned along with the config, so that the user can be told.
+pub fn load_or_recover_config() -> (ConfigData, Option<ConfigRecovery>) {
+    let error = match load_config() {
+        Ok(config) => return (config, None),
+        Err(ConfigError::Io(e)) if e.kind() == ErrorKind::NotFound => {
+            let new_config = ConfigData::default();
+            update_config(&new_config);
+            return (new_config, None);
+        }
+        Err(e) => e,
+    };
+    log::error!("Error while loading config: {}", error);
+
+    if let ConfigError::Invalid(_) = error {
+        let backup_path = get_config_backup_path();
+        match fs::rename(get_config_path(), &backup_path) {
+            Ok(()) => {
+                let new_config = ConfigData::default();
+                update_config(&new_config);
+                return (
+                    new_config,
+                    Some(ConfigRecovery::BackedUp { error, backup_path }),
+                );
             }
+            // Without a backup, the file is the only copy of the config
+            Err(e) => log::error!("Error while backing up config: {}", e),
         }
     }
+    CONFIG_KEPT.store(true, Ordering::Relaxed);
+    (ConfigData::default(), Some(ConfigRecovery::Kept(error)))
+}
 
-    let new_conf_positions = conf
-        .saved_positions
-        .into_iter()
-        .filter(|pos| pos.name != pos_name)
-        .collect();
-    conf.saved_positions = new_conf_positions;
+/// The config file loaded fine again, eg. after the user fixed it - it can be written over from now on
+pub fn release_kept_config() {
+    CONFIG_KEPT.store(false, Ordering::Relaxed);
+}
 
-    update_config(&conf);
-    conf
+/// Same as `load_or_recover_config`, for when there's nobody to tell about errors
+pub fn get_or_create_config() -> ConfigData {
+    load_or_recover_config().0
 }
 
-#[tauri::command]
-pub fn get_config() -> ConfigData {
-    let config_path = get_config_path().trim_end().to_string();
+/// Saves the config in one go - readers either see the old config or the new one, never a half-written file.
+/// While the app runs, go through `config_store` instead.
+pub fn write_config(updated_config: &ConfigData) -> Result<(), ConfigError> {
+    if CONFIG_KEPT.load(Ordering::Relaxed) {
+        return Err(ConfigError::Kept);
+    }
+    let config = ConfigData {
+        version: CURRENT_CONFIG_VERSION,
+        ..updated_config.clone()
+    };
+    let temp_path = get_config_temp_path();
+    fs::write(&temp_path, to_string::<ConfigData>(&config)?)?;
+    fs::rename(&temp_path, get_config_path())?;
+    Ok(())
+}
 
-    let old_conf_file = read_to_string(&config_path.to_string()).expect("Opening a config");
-    let stringified_new_config =
-        from_str::<ConfigData>(&old_conf_file).expect("Parsing opened config to struct");
+/// Same as `write_config`, for when there's nobody to tell about errors
+pub fn update_config(updated_config: &ConfigData) {
+    if let Err(e) = write_config(updated_config) {
+        log::error!("Error while saving config: {}", e);
+    }
+}
 
-    stringified_new_config
+/// Saves the desk to connect to on the next start, straight to the file - for use outside of the app
+pub fn save_local_name(
+    new_local_name: String,
+    peripheral_id: Option<String>,
+    address: Option<String>,
+) -> Result<(), ConfigError> {
+    let mut config = get_or_create_config();
+    config.select_desk(&new_local_name, peripheral_id, address);
+    write_config(&config)
 }
 
-pub fn update_config(updated_config: &ConfigData) {
-    let config_path = get_config_path().trim_end().to_string();
+#[tauri::command]
+pub fn remove_position(
+    app_handle: tauri::AppHandle,
+    pos_name: &str,
+) -> Result<ConfigData, AppError> {
+    let config = config_store::update_config(&app_handle, |conf| {
+        conf.saved_positions_mut()
+            .retain(|pos| pos.name != pos_name);
+        conf.clone()
+    })?;
+    Ok(config)
+}
 
-    let stringified_new_config = to_string::<ConfigData>(&updated_config).unwrap();
-    fs::write(config_path, stringified_new_config)
-        .expect("Saving a config after updating a config");
+/// Replaces the shortcut stopping the desk. Empty or missing shortcut disables it.
+#[tauri::command]
+pub fn set_stop_shortcut(
+    app_handle: tauri::AppHandle,
+    shortcut: Option<String>,
+) -> Result<ConfigData, AppError> {
+    let config = config_store::update_config(&app_handle, |conf| {
+        conf.stop_shortcut = shortcut.filter(|shortcut| !shortcut.is_empty());
+        conf.clone()
+    })?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn get_config(app_handle: tauri::AppHandle) -> ConfigData {
+    config_store::get_config(&app_handle)
 }
 
 #[tauri::command]
@@ -155,42 +633,113 @@ pub fn remove_config() {
     let _ = remove_file(config_path);
 }
 
+#[derive(Serialize, Debug)]
+pub struct PositionHeight {
+    pub name: String,
+    pub height: f32,
+}
+
+/// Heights of the desk in use, in the unit the user picked
+#[derive(Serialize, Debug)]
+pub struct HeightSettings {
+    pub unit: HeightUnit,
+    /// Calibration offset of the desk
+    pub offset: f32,
+    /// Height limits of the desk
+    pub min: f32,
+    pub max: f32,
+    /// Range the desk can physically move in, which the limits have to be within
+    pub hardware_min: f32,
+    pub hardware_max: f32,
+    pub positions: Vec<PositionHeight>,
+}
+
+impl HeightSettings {
+    pub fn new(config: &ConfigData) -> Self {
+        HeightSettings {
+            unit: config.height_unit,
+            offset: height::offset_in_unit(config.height_offset(), config.height_unit),
+            min: config.height_in_unit(config.height_limits().min),
+            max: config.height_in_unit(config.height_limits().max),
+            hardware_min: config.height_in_unit(HARDWARE_LIMITS.min),
+            hardware_max: config.height_in_unit(HARDWARE_LIMITS.max),
+            positions: config
+                .saved_positions()
+                .iter()
+                .map(|pos| PositionHeight {
+                    name: pos.name.clone(),
+                    height: config.height_in_unit(pos.value),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Runs the setup again on the next start, eg. to add another desk. Saved desks stay in the config.
 #[tauri::command]
-pub fn reset_desk() {
-    let config_path = get_config_path().trim_end().to_string();
+pub fn reset_desk(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    config_store::update_config(&app_handle, |conf| conf.local_name = None)?;
+    Ok(())
+}
 
-    let config =
-        read_to_string(&config_path).expect("err while reading config while resetting desk");
-    // Config exists
-    let config = from_str::<ConfigData>(config.as_str()).expect("Error while parsing config file");
+#[tauri::command]
+pub fn get_height_settings(app_handle: tauri::AppHandle) -> HeightSettings {
+    HeightSettings::new(&config_store::get_config(&app_handle))
+}
 
-    let updated_config = ConfigData {
-        local_name: None,
-        saved_positions: config.saved_positions,
-    };
+/// Changes the unit heights are shown in, and the calibration offset(in that unit) of the desk in use
+#[tauri::command]
+pub fn set_height_calibration(
+    app_handle: tauri::AppHandle,
+    unit: HeightUnit,
+    offset: f32,
+) -> Result<HeightSettings, AppError> {
+    let height_offset = height::offset_from_unit(offset, unit).ok_or(AppError::InvalidInput(
+        format!("Offset of {} {} is too big.", offset, unit.symbol()),
+    ))?;
+    let height_settings = config_store::update_config(&app_handle, |conf| {
+        conf.height_unit = unit;
+        conf.active_desk_mut().height_offset = height_offset;
+        HeightSettings::new(conf)
+    })?;
+    Ok(height_settings)
+}
 
-    let stringified_new_config = to_string::<ConfigData>(&updated_config).unwrap();
-    fs::write(config_path, stringified_new_config)
-        .expect("Saving a config after updating a config");
+/// Changes the height limits(in the unit the user picked) of the desk in use
+#[tauri::command]
+pub fn set_height_limits(
+    app_handle: tauri::AppHandle,
+    min: f32,
+    max: f32,
+) -> Result<HeightSettings, AppError> {
+    let height_limits = config_store::get_config(&app_handle).height_limits_from_unit(min, max)?;
+    let height_settings = config_store::update_config(&app_handle, |conf| {
+        conf.active_desk_mut().height_limits = height_limits;
+        HeightSettings::new(conf)
+    })?;
+    Ok(height_settings)
 }
 
 pub struct MenuConfigItem {
     pub position_elem: CustomMenuItem,
     pub name: String,
-    pub value: u16,
+    pub value: RawHeight,
     pub conf_item_title: String,
 }
 
 pub fn get_menu_items_from_config(config: &ConfigData) -> Vec<MenuConfigItem> {
     config
-        .saved_positions
+        .saved_positions()
         .iter()
         .map(|temp_conf_elem| {
             // Assign values so that they are not lost - TODO: figure out why the fuck does that even happen
             let name = &temp_conf_elem.name;
             let value = &temp_conf_elem.value;
             let conf_item_title = name.as_str().clone();
-            let position_elem = CustomMenuItem::new(conf_item_title, conf_item_title);
+            let position_elem = CustomMenuItem::new(
+                conf_item_title,
+                format!("{} ({})", conf_item_title, config.format_height(*value)),
+            );
             MenuConfigItem {
                 position_elem: position_elem.clone(),
                 name: name.clone(),
@@ -201,6 +750,27 @@ pub fn get_menu_items_from_config(config: &ConfigData) -> Vec<MenuConfigItem> {
         .collect::<Vec<MenuConfigItem>>()
 }
 
+pub fn group_position_id(group: &DeskGroup, position: &Position) -> String {
+    format!(
+        "{}{}/{}",
+        GROUP_POSITION_ID_PREFIX, group.name, position.name
+    )
+}
+
+/// Group and position behind a tray item created by `create_main_tray_menu`
+pub fn find_group_position<'a>(
+    config: &'a ConfigData,
+    item_id: &str,
+) -> Option<(&'a DeskGroup, &'a Position)> {
+    config.groups.iter().find_map(|group| {
+        group
+            .saved_positions
+            .iter()
+            .find(|position| group_position_id(group, position) == item_id)
+            .map(|position| (group, position))
+    })
+}
+
 /**
 Utility function returning the tray menu instance, based on the provided config
 */
@@ -225,15 +795,156 @@ pub fn create_main_tray_menu(config: &ConfigData) -> SystemTrayMenu {
     let positions_submenu = SystemTraySubmenu::new("Positions", sys_tray_menu);
 
     let header_item = CustomMenuItem::new(HEADER_ID.to_string(), "Idasen Controller").disabled();
+    let stop_item = CustomMenuItem::new(STOP_ID.to_string(), "Stop desk");
     let about_item = CustomMenuItem::new(ABOUT_ID.to_string(), "About/Options");
     let quit_item = CustomMenuItem::new(QUIT_ID.to_string(), "Quit");
-    let main_menu = SystemTrayMenu::new()
+    let mut main_menu = SystemTrayMenu::new()
         .add_item(header_item)
         .add_native_item(SystemTrayMenuItem::Separator)
-        .add_submenu(positions_submenu)
+        .add_item(stop_item)
+        .add_submenu(positions_submenu);
+
+    for group in &config.groups {
+        let mut group_menu = SystemTrayMenu::new();
+        for position in &group.saved_positions {
+            group_menu = group_menu.add_item(CustomMenuItem::new(
+                group_position_id(group, position),
+                position.name.clone(),
+            ));
+        }
+        main_menu = main_menu.add_submenu(SystemTraySubmenu::new(
+            format!("Group: {}", group.name),
+            group_menu,
+        ));
+    }
+
+    let desk_names = config.desk_names();
+    if !desk_names.is_empty() {
+        let mut desks_menu = SystemTrayMenu::new();
+        for desk_name in desk_names {
+            let mut desk_item = CustomMenuItem::new(
+                format!("{}{}", SWITCH_DESK_ID_PREFIX, desk_name),
+                desk_name.clone(),
+            );
+            if config.local_name.as_ref() == Some(&desk_name) {
+                desk_item = desk_item.selected();
+            }
+            desks_menu = desks_menu.add_item(desk_item);
+        }
+        main_menu = main_menu.add_submenu(SystemTraySubmenu::new("Switch desk", desks_menu));
+    }
+
+    // Title gets updated to the actual pause state once the menu is set
+    if config.reminders.is_some() {
+        let pause_reminders_item =
+            CustomMenuItem::new(PAUSE_REMINDERS_ID.to_string(), "Pause reminders");
+        main_menu = main_menu.add_item(pause_reminders_item);
+    }
+
+    main_menu
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(about_item)
-        .add_item(quit_item.clone());
+        .add_item(quit_item.clone())
+}
 
-    main_menu
+#[cfg(test)]
+mod config_suite {
+    use crate::{
+        config_utils::{parse_config, ConfigData, ConfigError, CURRENT_CONFIG_VERSION},
+        height::{HeightLimits, RawHeight},
+        loose_idasen::HARDWARE_LIMITS,
+    };
+
+    #[test]
+    fn should_migrate_unversioned_config() {
+        let config = parse_config(
+            r#"{"local_name": "Desk 1234", "saved_positions": [{"name": "Standing", "value": 11000, "shortcut": ""}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.version, CURRENT_CONFIG_VERSION);
+        assert_eq!(config.local_name, Some("Desk 1234".to_string()));
+        assert_eq!(config.saved_positions()[0].shortcut, None);
+        assert_eq!(config.stop_shortcut, None);
+    }
+
+    #[test]
+    fn should_move_positions_into_desk_profile() {
+        let config = parse_config(
+            r#"{"version": 1, "local_name": "Desk 1234", "saved_positions": [{"name": "Standing", "value": 11000, "shortcut": null}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.desks.len(), 1);
+        assert_eq!(config.desks[0].name, "Desk 1234");
+        assert_eq!(config.saved_positions()[0].name, "Standing");
+
+        let mut config = config;
+        config.select_desk("Desk 5678", None, None);
+        assert!(config.saved_positions().is_empty());
+        assert_eq!(config.desk_names(), vec!["Desk 5678", "Desk 1234"]);
+    }
+
+    #[test]
+    fn should_give_unnamed_positions_to_first_desk() {
+        let mut config = parse_config(
+            r#"{"version": 1, "local_name": null, "saved_positions": [{"name": "Standing", "value": 11000, "shortcut": null}]}"#,
+        )
+        .unwrap();
+        assert!(config.desk_names().is_empty());
+
+        config.select_desk(
+            "Desk 1234",
+            Some("hci0/dev_C2_6D_88_A4_2B_01".to_string()),
+            Some("C2:6D:88:A4:2B:01".to_string()),
+        );
+
+        assert_eq!(config.desks.len(), 1);
+        assert_eq!(config.saved_positions()[0].name, "Standing");
+        assert_eq!(
+            config.desks[0].peripheral_id.as_deref(),
+            Some("hci0/dev_C2_6D_88_A4_2B_01")
+        );
+        assert_eq!(
+            config.desks[0].address.as_deref(),
+            Some("C2:6D:88:A4:2B:01")
+        );
+    }
+
+    #[test]
+    fn should_reject_broken_config() {
+        let config = parse_config(r#"{"local_name": "Desk 1234", "saved_positions": [{"#);
+
+        assert!(matches!(config, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn should_reject_config_from_newer_version() {
+        let config = parse_config(r#"{"version": 999, "local_name": null}"#);
+
+        assert!(matches!(config, Err(ConfigError::UnsupportedVersion(999))));
+    }
+
+    #[test]
+    fn should_keep_height_limits_per_desk() {
+        let mut config = ConfigData::default();
+        config.select_desk("Desk 1234", None, None);
+        let height_limits = config.height_limits_from_unit(65.0, 110.0).unwrap();
+        config.active_desk_mut().height_limits = height_limits;
+
+        let limits = HeightLimits {
+            min: RawHeight(6500),
+            max: RawHeight(11000),
+        };
+        assert_eq!(config.height_limits(), limits);
+        assert_eq!(config.desk_height_limits("Desk 5678"), HARDWARE_LIMITS);
+        assert_eq!(
+            config.raw_height_in_limits(100.0).unwrap(),
+            RawHeight(10000)
+        );
+        assert!(config.raw_height_in_limits(120.0).is_err());
+        // Limits past the range of the desk mean no limits
+        assert_eq!(config.height_limits_from_unit(0.0, 200.0).unwrap(), None);
+        assert!(config.height_limits_from_unit(110.0, 65.0).is_err());
+    }
 }
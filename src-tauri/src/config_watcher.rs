@@ -0,0 +1,80 @@
+/*
+  Watches the config file for changes made outside of the app(eg. by hand, or by syncing dotfiles) and reloads them.
+  A change which cannot be loaded is ignored - the last good config stays in use and the user gets notified.
+*/
+use std::{io::ErrorKind, path::Path, time::Duration};
+
+use notify::{RecursiveMode, Watcher};
+use tauri::AppHandle;
+
+use crate::{
+    config_store,
+    config_utils::{self, ConfigError},
+    tray_utils,
+};
+
+/// Editors tend to save a file in a few steps - wait for them to finish before reading it
+const DEBOUNCE_TIME: Duration = Duration::from_millis(300);
+
+pub fn spawn_config_watcher(app_handle: AppHandle) {
+    let config_path = config_utils::get_config_path();
+    let config_path = Path::new(&config_path);
+    let (Some(config_dir), Some(config_file_name)) =
+        (config_path.parent(), config_path.file_name())
+    else {
+        return;
+    };
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let config_file_name = config_file_name.to_os_string();
+    let watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let is_config_event = event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == Some(config_file_name.as_os_str()));
+                if is_config_event {
+                    _ = sender.send(());
+                }
+            }
+            Err(e) => log::warn!("Error while watching config: {}", e),
+        });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            log::error!("Error while starting config watcher: {}", e);
+            return;
+        }
+    };
+    // Config gets replaced rather than written to(by the app and by most editors), so the whole directory is watched
+    if let Err(e) = watcher.watch(config_dir, RecursiveMode::NonRecursive) {
+        log::error!("Error while watching config: {}", e);
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        // Watching stops once the watcher gets dropped
+        let _watcher = watcher;
+        while receiver.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE_TIME).await;
+            while receiver.try_recv().is_ok() {}
+
+            match config_store::reload_config(&app_handle) {
+                Ok(()) => {}
+                // Config gets removed when resetting the app
+                Err(ConfigError::Io(e)) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    log::error!("Error while reloading config: {}", e);
+                    tray_utils::show_notification(
+                        &app_handle,
+                        &format!(
+                            "Your config file could not be loaded, so the previous config is still used. ({})",
+                            e
+                        ),
+                    );
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,144 @@
+/*
+  Desk groups - several desks moving to the same position at once, eg. in a meeting room.
+  Every desk moves on its own, so a desk which cannot be reached does not hold back the rest of the group.
+*/
+use futures::future::join_all;
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::{
+    app_error::AppError, config_store, config_utils::DeskGroup, desk_registry, height::RawHeight,
+    loose_idasen::BtError, movement_utils, tray_utils,
+};
+
+/// How a single desk of the group did
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DeskMoveResult {
+    pub desk: String,
+    /// `None` when the desk arrived
+    pub error: Option<String>,
+}
+
+/// Moves every desk of the group at the same time. Resolves once all of them are done.
+pub async fn move_group_to_target(
+    app_handle: &AppHandle,
+    group: &DeskGroup,
+    target_position: RawHeight,
+) -> Vec<DeskMoveResult> {
+    let movements = group.desks.iter().map(|desk_name| async move {
+        let movement = async {
+            let desk = desk_registry::get_or_connect_desk(app_handle, desk_name).await?;
+            movement_utils::move_desk_to_target(app_handle, desk_name, &desk, target_position).await
+        };
+        DeskMoveResult {
+            desk: desk_name.clone(),
+            error: movement.await.err().map(|e: BtError| e.to_string()),
+        }
+    });
+    join_all(movements).await
+}
+
+/// Message about the desks which did not arrive, if any
+fn summarize(group: &DeskGroup, results: &[DeskMoveResult]) -> Option<String> {
+    let failed = results
+        .iter()
+        .filter_map(|result| {
+            let error = result.error.as_ref()?;
+            Some(format!("{} ({})", result.desk, error))
+        })
+        .collect::<Vec<String>>();
+    if failed.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "Not every desk of {} arrived: {}",
+        group.name,
+        failed.join(", ")
+    ))
+}
+
+/// Same as `move_group_to_target`, but in the background - for the tray and the shortcuts
+pub fn spawn_move_group_to_target(
+    app_handle: &AppHandle,
+    group: DeskGroup,
+    target_position: RawHeight,
+) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let results = move_group_to_target(&app_handle, &group, target_position).await;
+        log::info!("Group {} moved: {:?}", group.name, results);
+        if let Some(message) = summarize(&group, &results) {
+            tray_utils::show_notification(&app_handle, &message);
+        }
+    });
+}
+
+/// Moves every desk of the group to one of the positions of the group
+pub async fn move_group_to_preset(
+    app_handle: &AppHandle,
+    group_name: &str,
+    preset_name: &str,
+) -> Result<Vec<DeskMoveResult>, AppError> {
+    let config = config_store::get_config(app_handle);
+    let group = config
+        .groups
+        .iter()
+        .find(|group| group.name == group_name)
+        .ok_or_else(|| AppError::NotFound(format!("No desk group named `{}`.", group_name)))?;
+    let position = group
+        .saved_positions
+        .iter()
+        .find(|pos| pos.name == preset_name)
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No position named `{}` in the group `{}`.",
+                preset_name, group_name
+            ))
+        })?;
+    Ok(move_group_to_target(app_handle, group, position.value).await)
+}
+
+#[tauri::command]
+pub async fn move_group(
+    app_handle: AppHandle,
+    group: String,
+    preset: String,
+) -> Result<Vec<DeskMoveResult>, AppError> {
+    move_group_to_preset(&app_handle, &group, &preset).await
+}
+
+#[cfg(test)]
+mod groups_suite {
+    use crate::{
+        config_utils::DeskGroup,
+        desk_groups::{summarize, DeskMoveResult},
+    };
+
+    #[test]
+    fn should_only_mention_desks_which_failed() {
+        let group = DeskGroup {
+            name: "Meeting room".to_string(),
+            desks: vec!["Desk 1".to_string(), "Desk 2".to_string()],
+            saved_positions: vec![],
+        };
+        let results = [
+            DeskMoveResult {
+                desk: "Desk 1".to_string(),
+                error: None,
+            },
+            DeskMoveResult {
+                desk: "Desk 2".to_string(),
+                error: Some("Cannot find the device.".to_string()),
+            },
+        ];
+
+        assert_eq!(summarize(&group, &results[..1]), None);
+        assert_eq!(
+            summarize(&group, &results),
+            Some(
+                "Not every desk of Meeting room arrived: Desk 2 (Cannot find the device.)"
+                    .to_string()
+            )
+        );
+    }
+}
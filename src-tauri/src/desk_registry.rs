@@ -0,0 +1,94 @@
+/*
+  Desks connected right now, keyed by their name. The desk in use(`local_name` in the config) is kept connected by the supervisor,
+  other desks(eg. the rest of a desk group) get connected the first time they're needed.
+*/
+use std::{collections::HashMap, sync::Mutex};
+
+use btleplug::api::Peripheral as ApiPeripheral;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    config_store,
+    config_utils::DeskProfile,
+    loose_idasen::{self, ConnectedDesk},
+};
+
+#[derive(Default)]
+pub struct TauriDeskRegistry(Mutex<HashMap<String, ConnectedDesk>>);
+
+impl TauriDeskRegistry {
+    pub fn new() -> Self {
+        TauriDeskRegistry(Mutex::new(HashMap::new()))
+    }
+
+    pub fn get(&self, desk_name: &str) -> Option<ConnectedDesk> {
+        let desks = self.0.lock().expect("Error while unwrapping desk registry");
+        desks.get(desk_name).cloned()
+    }
+
+    /// Adds the desk, replacing the previous connection to it
+    pub fn insert(&self, desk_name: String, desk: ConnectedDesk) {
+        let mut desks = self.0.lock().expect("Error while unwrapping desk registry");
+        desks.insert(desk_name, desk);
+    }
+
+    pub fn remove(&self, desk_name: &str) {
+        let mut desks = self.0.lock().expect("Error while unwrapping desk registry");
+        desks.remove(desk_name);
+    }
+
+    pub fn all(&self) -> Vec<(String, ConnectedDesk)> {
+        let desks = self.0.lock().expect("Error while unwrapping desk registry");
+        desks
+            .iter()
+            .map(|(desk_name, desk)| (desk_name.clone(), desk.clone()))
+            .collect()
+    }
+}
+
+pub fn get_desk(app_handle: &AppHandle, desk_name: &str) -> Option<ConnectedDesk> {
+    app_handle.state::<TauriDeskRegistry>().get(desk_name)
+}
+
+/// The desk in use, if it's connected
+pub fn get_active_desk(app_handle: &AppHandle) -> Option<ConnectedDesk> {
+    let local_name = config_store::get_config(app_handle).local_name?;
+    get_desk(app_handle, &local_name)
+}
+
+pub fn insert_desk(app_handle: &AppHandle, desk_name: String, desk: ConnectedDesk) {
+    app_handle
+        .state::<TauriDeskRegistry>()
+        .insert(desk_name, desk);
+}
+
+pub fn remove_desk(app_handle: &AppHandle, desk_name: &str) {
+    app_handle.state::<TauriDeskRegistry>().remove(desk_name);
+}
+
+pub fn connected_desks(app_handle: &AppHandle) -> Vec<(String, ConnectedDesk)> {
+    app_handle.state::<TauriDeskRegistry>().all()
+}
+
+/// The desk with the provided name, connecting to it first when it's not connected(anymore)
+pub async fn get_or_connect_desk(
+    app_handle: &AppHandle,
+    desk_name: &str,
+) -> Result<ConnectedDesk, loose_idasen::BtError> {
+    if let Some(desk) = get_desk(app_handle, desk_name) {
+        if desk.device_instance.is_connected().await.unwrap_or(false) {
+            return Ok(desk);
+        }
+    }
+
+    let config = config_store::get_config(app_handle);
+    let saved_desk = config
+        .desks
+        .iter()
+        .find(|desk| desk.name == desk_name)
+        .cloned()
+        .unwrap_or_else(|| DeskProfile::new(desk_name.to_string()));
+    let desk = loose_idasen::connect_to_saved_desk(&saved_desk, &config.adapter).await?;
+    insert_desk(app_handle, desk_name.to_string(), desk.clone());
+    Ok(desk)
+}
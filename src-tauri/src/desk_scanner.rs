@@ -0,0 +1,230 @@
+/*
+  Scan session for the setup window. Desks are sent to the frontend as events as soon as an adapter sees them,
+  instead of waiting for a fixed scan to end. Only one session runs at a time - starting a new one cancels the previous.
+*/
+use std::{collections::HashSet, pin::Pin, sync::Mutex, time::Duration};
+
+use btleplug::{
+    api::{Central, CentralEvent, ScanFilter},
+    platform::{Adapter, Peripheral as PlatformPeripheral},
+};
+use futures::{stream::select_all, Stream, StreamExt};
+use serde::Serialize;
+use tauri::{AppHandle, Manager as TauriManager};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config_store,
+    loose_idasen::{self, AdapterError, BtError, PotentialDesk, DESK_SERVICE_UUID},
+};
+
+pub const DESK_FOUND_EVENT: &str = "desk-found";
+pub const SCAN_FINISHED_EVENT: &str = "desk-scan-finished";
+
+/// For how long a session scans when the frontend does not say otherwise
+const DEFAULT_SCAN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ScanFinished {
+    /// Set when the scan broke off, eg. because bluetooth is off
+    pub error: Option<String>,
+    /// Adapters which could not scan - the rest kept going
+    pub adapter_errors: Vec<AdapterError>,
+}
+
+#[derive(Default)]
+struct ScanSession {
+    /// Counts up with every session, so that a session can tell when it has been replaced
+    number: u64,
+    cancel_token: Option<CancellationToken>,
+}
+
+#[derive(Default)]
+pub struct TauriScanState(Mutex<ScanSession>);
+
+impl TauriScanState {
+    pub fn new() -> Self {
+        TauriScanState(Mutex::new(ScanSession::default()))
+    }
+
+    /// Cancel the session in progress(if any) and hand out the number and token for the next one
+    fn replace_current(&self) -> (u64, CancellationToken) {
+        let mut session = self.0.lock().expect("Error while unwrapping scan state");
+        if let Some(previous_scan) = session.cancel_token.take() {
+            previous_scan.cancel();
+        }
+
+        let cancel_token = CancellationToken::new();
+        session.number += 1;
+        session.cancel_token = Some(cancel_token.clone());
+        (session.number, cancel_token)
+    }
+
+    fn cancel_current(&self) {
+        let mut session = self.0.lock().expect("Error while unwrapping scan state");
+        if let Some(current_scan) = session.cancel_token.take() {
+            current_scan.cancel();
+        }
+    }
+
+    /// Ends the session, unless a newer one has replaced it. Returns whether it was still the current one.
+    fn finish(&self, session_number: u64) -> bool {
+        let mut session = self.0.lock().expect("Error while unwrapping scan state");
+        if session.number != session_number {
+            return false;
+        }
+        session.cancel_token = None;
+        true
+    }
+}
+
+/// Starts a scan session - every desk found is sent with `DESK_FOUND_EVENT`, and `SCAN_FINISHED_EVENT` is sent once it ends.
+/// Ends after the provided number of seconds, or when cancelled.
+#[tauri::command]
+pub fn start_desk_scan(app_handle: AppHandle, timeout_secs: Option<u64>) {
+    let timeout = timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SCAN_TIMEOUT);
+    let (session_number, cancel_token) = app_handle.state::<TauriScanState>().replace_current();
+
+    tauri::async_runtime::spawn(async move {
+        let mut adapter_errors = Vec::new();
+        let scan_result = tokio::select! {
+            scan_result = scan_for_desks(&app_handle, &mut adapter_errors) => scan_result,
+            _ = tokio::time::sleep(timeout) => Ok(()),
+            _ = cancel_token.cancelled() => Ok(()),
+        };
+        if let Err(e) = &scan_result {
+            log::error!("Error while scanning for desks: {}", e);
+        }
+        // A newer session has taken over the adapters - it will report when it's done
+        if !app_handle.state::<TauriScanState>().finish(session_number) {
+            return;
+        }
+        stop_scans().await;
+        let scan_finished = ScanFinished {
+            error: scan_result.err().map(|e| e.to_string()),
+            adapter_errors,
+        };
+        _ = app_handle.emit_all(SCAN_FINISHED_EVENT, scan_finished);
+    });
+}
+
+#[tauri::command]
+pub fn cancel_desk_scan(app_handle: AppHandle) {
+    app_handle.state::<TauriScanState>().cancel_current();
+}
+
+/// Sends every desk the adapters see, once. Keeps going until the adapters stop sending events, or none of them can scan.
+/// Adapters which fail are skipped and put into `adapter_errors`.
+async fn scan_for_desks(
+    app_handle: &AppHandle,
+    adapter_errors: &mut Vec<AdapterError>,
+) -> Result<(), BtError> {
+    let mut adapters = Vec::new();
+    let mut adapter_events = Vec::new();
+    let preferred_adapter = config_store::get_config(app_handle).adapter;
+    for (adapter_name, adapter) in loose_idasen::get_adapters(&preferred_adapter).await? {
+        match start_scan(&adapter).await {
+            Ok(events) => {
+                let events_adapter = adapter.clone();
+                adapter_events.push(events.map(move |event| (events_adapter.clone(), event)));
+                adapters.push(adapter);
+            }
+            Err(e) => {
+                log::warn!("Error while scanning with adapter {}: {}", adapter_name, e);
+                adapter_errors.push(AdapterError {
+                    adapter: adapter_name,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+    if adapters.is_empty() && !adapter_errors.is_empty() {
+        return Err(BtError::AdaptersFailed(adapter_errors.clone()));
+    }
+
+    let mut sent_desks = HashSet::new();
+    // Desks seen by an earlier scan are not discovered again
+    for adapter in &adapters {
+        for peripheral in adapter.peripherals().await.unwrap_or_default() {
+            send_if_desk(app_handle, peripheral, &mut sent_desks).await;
+        }
+    }
+
+    let mut adapter_events = select_all(adapter_events);
+    while let Some((adapter, event)) = adapter_events.next().await {
+        // Some devices only advertise their name in an update after being discovered
+        if let CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id) = event {
+            if let Ok(peripheral) = adapter.peripheral(&id).await {
+                send_if_desk(app_handle, peripheral, &mut sent_desks).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn start_scan(
+    adapter: &Adapter,
+) -> Result<Pin<Box<dyn Stream<Item = CentralEvent> + Send>>, BtError> {
+    let events = adapter.events().await?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![DESK_SERVICE_UUID],
+        })
+        .await?;
+    Ok(events)
+}
+
+async fn send_if_desk(
+    app_handle: &AppHandle,
+    peripheral: PlatformPeripheral,
+    sent_desks: &mut HashSet<String>,
+) {
+    let desk = match loose_idasen::expand_if_desk(peripheral, &None).await {
+        Ok(Some(desk)) => desk,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("Error while reading a scanned device: {}", e);
+            return;
+        }
+    };
+    if !sent_desks.insert(desk.id.clone()) {
+        return;
+    }
+
+    let saved_desks = config_store::get_config(app_handle).saved_desks();
+    let potential_desk = PotentialDesk::new(&desk, &saved_desks);
+    log::debug!("Found desk: {:?}", potential_desk);
+    _ = app_handle.emit_all(DESK_FOUND_EVENT, potential_desk);
+}
+
+async fn stop_scans() {
+    // Every adapter, in case the preferred one changed while scanning
+    for (adapter_name, adapter) in loose_idasen::get_adapters(&None).await.unwrap_or_default() {
+        if let Err(e) = adapter.stop_scan().await {
+            log::warn!(
+                "Error while stopping a scan with adapter {}: {}",
+                adapter_name,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod scanner_suite {
+    use crate::desk_scanner::TauriScanState;
+
+    #[test]
+    fn should_cancel_replaced_session() {
+        let scan_state = TauriScanState::new();
+        let (first_session, first_token) = scan_state.replace_current();
+        let (second_session, second_token) = scan_state.replace_current();
+
+        assert!(first_token.is_cancelled());
+        assert!(!second_token.is_cancelled());
+        assert!(!scan_state.finish(first_session));
+        assert!(scan_state.finish(second_session));
+    }
+}
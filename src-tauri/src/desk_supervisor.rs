@@ -0,0 +1,249 @@
+/*
+  Background task keeping the desk in use connected.
+  Watches for the desk dropping off(eg. when it goes to sleep or the laptop leaves the room),
+  then reconnects to whichever saved desk is nearby with a backoff and puts it into the desk registry.
+*/
+use std::{sync::Mutex, time::Duration};
+
+use btleplug::{
+    api::{Central, CentralEvent, Manager as ApiManager, Peripheral as ApiPeripheral},
+    platform::Manager,
+};
+use futures::{stream::select_all, StreamExt};
+use serde::Serialize;
+use tauri::{AppHandle, Manager as TauriManager};
+
+use crate::{
+    app_error, config_store, config_utils, desk_registry,
+    loose_idasen::{self, ConnectedDesk},
+    position_feed, tray_height,
+};
+
+pub const CONNECTION_CHANGED_EVENT: &str = "desk-connection-changed";
+
+/// How often we ask the desk whether it's still connected, in case the adapter does not report disconnects
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// How often we check for a desk when none has been set up yet
+const SETUP_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// No desk has been saved yet - setup has to be done first
+    NotConfigured,
+    Connected,
+    Disconnected,
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
+impl ConnectionState {
+    /// Height of the desk is only shown while it's connected
+    pub fn tray_title(&self, desk_height: Option<String>) -> String {
+        match self {
+            ConnectionState::NotConfigured => "Idasen Controller (no desk)".to_string(),
+            ConnectionState::Connected => match desk_height {
+                Some(desk_height) => format!("Idasen Controller - {}", desk_height),
+                None => "Idasen Controller".to_string(),
+            },
+            ConnectionState::Disconnected => "Idasen Controller (disconnected)".to_string(),
+            ConnectionState::Reconnecting { attempt } => {
+                format!("Idasen Controller (reconnecting, attempt {})", attempt)
+            }
+        }
+    }
+}
+
+pub struct TauriConnectionState(pub Mutex<ConnectionState>);
+
+#[tauri::command]
+pub fn get_connection_state(app_handle: AppHandle) -> ConnectionState {
+    let connection_state = app_handle.state::<TauriConnectionState>();
+    let connection_state = connection_state
+        .0
+        .lock()
+        .expect("Error while unwrapping connection state");
+    connection_state.clone()
+}
+
+/// Updates the connection state and lets the frontend and the tray know about it
+pub fn set_connection_state(app_handle: &AppHandle, new_state: ConnectionState) {
+    {
+        let connection_state = app_handle.state::<TauriConnectionState>();
+        let mut connection_state = connection_state
+            .0
+            .lock()
+            .expect("Error while unwrapping connection state");
+        if *connection_state == new_state {
+            return;
+        }
+        *connection_state = new_state.clone();
+    }
+
+    log::info!("Desk connection state: {:?}", new_state);
+    _ = app_handle.emit_all(CONNECTION_CHANGED_EVENT, new_state);
+    update_tray_header(app_handle);
+}
+
+/// Puts the current connection state and height into the tray header and tooltip. Has to be called every time the tray menu is recreated.
+pub fn update_tray_header(app_handle: &AppHandle) {
+    let desk_height = position_feed::latest_position(app_handle).map(|position_speed| {
+        tray_height::describe_height(&position_speed, &config_store::get_config(app_handle))
+    });
+    let title = get_connection_state(app_handle.clone()).tray_title(desk_height);
+    let tray_handle = app_handle.tray_handle();
+    _ = tray_handle
+        .get_item(config_utils::HEADER_ID)
+        .set_title(&title);
+    // Not supported on Linux
+    _ = tray_handle.set_tooltip(&title);
+}
+
+/// Delay before the provided(counted from 1) reconnection attempt - doubles every attempt, up to a limit
+pub fn reconnect_delay(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    (MIN_RECONNECT_DELAY * 2u32.pow(exponent)).min(MAX_RECONNECT_DELAY)
+}
+
+pub fn spawn_desk_supervisor(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let local_name = config_store::get_config(&app_handle).local_name;
+            let active_desk = local_name.and_then(|local_name| {
+                desk_registry::get_desk(&app_handle, &local_name).map(|desk| (local_name, desk))
+            });
+            if let Some((desk_name, desk)) = active_desk {
+                set_connection_state(&app_handle, ConnectionState::Connected);
+                // Position is only fed for as long as the desk stays connected
+                tokio::select! {
+                    _ = wait_for_disconnect(&desk) => {}
+                    _ = position_feed::forward_positions(&app_handle, &desk) => {}
+                }
+                log::warn!("Desk disconnected: {}", desk_name);
+                desk_registry::remove_desk(&app_handle, &desk_name);
+                position_feed::clear(&app_handle);
+                set_connection_state(&app_handle, ConnectionState::Disconnected);
+            }
+
+            // Config might have changed since the last loop - always use the freshest desk name
+            if config_store::get_config(&app_handle).local_name.is_some() {
+                reconnect(&app_handle).await;
+            } else {
+                set_connection_state(&app_handle, ConnectionState::NotConfigured);
+                tokio::time::sleep(SETUP_CHECK_INTERVAL).await;
+            }
+        }
+    });
+}
+
+/// Resolves once the desk is gone
+async fn wait_for_disconnect(desk: &ConnectedDesk) {
+    let desk_id = desk.device_instance.id();
+    let mut adapter_events = Vec::new();
+    if let Ok(manager) = Manager::new().await {
+        for adapter in manager.adapters().await.unwrap_or_default() {
+            if let Ok(events) = adapter.events().await {
+                adapter_events.push(events);
+            }
+        }
+    }
+    let mut adapter_events = select_all(adapter_events);
+    let mut health_check = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            Some(event) = adapter_events.next() => {
+                if let CentralEvent::DeviceDisconnected(id) = event {
+                    if id == desk_id {
+                        return;
+                    }
+                }
+            }
+            _ = health_check.tick() => {
+                if !desk.device_instance.is_connected().await.unwrap_or(false) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Keeps trying to connect to one of the saved desks until it succeeds. The desk in use is tried first.
+async fn reconnect(app_handle: &AppHandle) {
+    let mut attempt = 1;
+    loop {
+        set_connection_state(app_handle, ConnectionState::Reconnecting { attempt });
+        let config = config_store::get_config(app_handle);
+        match loose_idasen::connect_to_any_desk(&config.saved_desks(), &config.adapter).await {
+            Ok((desk_name, desk)) => {
+                let peripheral_id = desk.peripheral_id.clone();
+                let address = desk.address();
+                desk_registry::insert_desk(app_handle, desk_name.clone(), desk);
+                // Another saved desk might have been nearby, eg. after moving between home and office
+                if !config_store::get_config(app_handle).is_desk_in_use(
+                    &desk_name,
+                    &peripheral_id,
+                    address.as_deref(),
+                ) {
+                    if let Err(e) = config_store::update_config(app_handle, |config| {
+                        config.select_desk(&desk_name, Some(peripheral_id), address)
+                    }) {
+                        log::error!("Error while saving the desk in use: {}", e);
+                    }
+                }
+                log::info!("Reconnected to desk: {}", desk_name);
+                return;
+            }
+            Err(e) => {
+                log::warn!("Reconnection attempt {} failed: {}", attempt, e);
+                tokio::time::sleep(reconnect_delay(attempt)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Makes the desk the one in use, and drops the current connection so that the supervisor connects to it instead
+pub fn switch_desk(app_handle: &AppHandle, desk_name: &str) {
+    let previous_desk_name = config_store::get_config(app_handle).local_name;
+    if previous_desk_name.as_deref() == Some(desk_name) {
+        return;
+    }
+    if let Err(e) = config_store::update_config(app_handle, |config| {
+        config.select_desk(desk_name, None, None)
+    }) {
+        app_error::report(app_handle, "Error while switching desks", &e.into());
+        return;
+    }
+    log::info!("Switching to desk: {}", desk_name);
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let previous_desk = previous_desk_name
+            .and_then(|desk_name| desk_registry::get_desk(&app_handle, &desk_name));
+        if let Some(desk) = previous_desk {
+            if let Err(e) = desk.device_instance.disconnect().await {
+                log::warn!("Error while disconnecting from the previous desk: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod reconnecting_suite {
+    use std::time::Duration;
+
+    use crate::desk_supervisor::reconnect_delay;
+
+    #[test]
+    fn should_back_off_exponentially_up_to_a_limit() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(5), Duration::from_secs(16));
+        assert_eq!(reconnect_delay(7), Duration::from_secs(60));
+        assert_eq!(reconnect_delay(u32::MAX), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,96 @@
+/*
+  Thin abstraction over the bluetooth peripheral a desk lives behind.
+  Everything in `loose_idasen` talks to the desk through this trait, so that the desk logic can run
+  against a real `btleplug` peripheral as well as an in-memory simulated desk(see `simulated_desk`).
+*/
+use std::{collections::BTreeSet, fmt::Debug, pin::Pin};
+
+use async_trait::async_trait;
+use btleplug::{
+    api::{BDAddr, Characteristic, Peripheral as ApiPeripheral, ValueNotification, WriteType},
+    platform::Peripheral as PlatformPeripheral,
+};
+use futures::Stream;
+
+pub type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+/// The subset of `btleplug::api::Peripheral` the desk logic actually needs
+#[async_trait]
+pub trait DeskTransport: Clone + Debug + Send + Sync + 'static {
+    /// Stable id of the peripheral - the address on Linux and Windows, a UUID assigned by the system on macOS
+    fn peripheral_id(&self) -> String;
+
+    /// Bluetooth address of the peripheral. Always `00:00:00:00:00:00` on macOS.
+    fn address(&self) -> BDAddr;
+
+    async fn connect(&self) -> btleplug::Result<()>;
+
+    async fn is_connected(&self) -> btleplug::Result<bool>;
+
+    async fn discover_services(&self) -> btleplug::Result<()>;
+
+    /// Characteristics found during the last `discover_services` call
+    fn characteristics(&self) -> BTreeSet<Characteristic>;
+
+    async fn read(&self, characteristic: &Characteristic) -> btleplug::Result<Vec<u8>>;
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> btleplug::Result<()>;
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()>;
+
+    /// Stream of notifications for all subscribed characteristics
+    async fn notifications(&self) -> btleplug::Result<NotificationStream>;
+}
+
+#[async_trait]
+impl DeskTransport for PlatformPeripheral {
+    fn peripheral_id(&self) -> String {
+        ApiPeripheral::id(self).to_string()
+    }
+
+    fn address(&self) -> BDAddr {
+        ApiPeripheral::address(self)
+    }
+
+    async fn connect(&self) -> btleplug::Result<()> {
+        ApiPeripheral::connect(self).await
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        ApiPeripheral::is_connected(self).await
+    }
+
+    async fn discover_services(&self) -> btleplug::Result<()> {
+        ApiPeripheral::discover_services(self).await
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        ApiPeripheral::characteristics(self)
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> btleplug::Result<Vec<u8>> {
+        ApiPeripheral::read(self, characteristic).await
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> btleplug::Result<()> {
+        ApiPeripheral::write(self, characteristic, data, write_type).await
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        ApiPeripheral::subscribe(self, characteristic).await
+    }
+
+    async fn notifications(&self) -> btleplug::Result<NotificationStream> {
+        ApiPeripheral::notifications(self).await
+    }
+}
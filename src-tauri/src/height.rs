@@ -0,0 +1,150 @@
+/*
+  Heights of the desk. The desk reports and moves to raw heights, while the user sees the height of their tabletop -
+  the raw height with the calibration offset of the desk applied, in the unit they picked.
+*/
+use std::fmt;
+
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HeightUnit {
+    #[default]
+    Cm,
+    Inches,
+}
+
+impl HeightUnit {
+    fn tenth_mm(&self) -> f32 {
+        match self {
+            HeightUnit::Cm => 100.0,
+            HeightUnit::Inches => 254.0,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            HeightUnit::Cm => "cm",
+            HeightUnit::Inches => "in",
+        }
+    }
+}
+
+/// Height as the desk reports it, in tenth mm. Saved as a plain number.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct RawHeight(pub u16);
+
+impl RawHeight {
+    /// Height of the tabletop, with the calibration offset(in tenth mm) of the desk applied
+    pub fn calibrate(self, height_offset: i16) -> Height {
+        Height(self.0 as i32 + height_offset as i32)
+    }
+}
+
+impl fmt::Display for RawHeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Range the desk is allowed to move in, in raw heights - both ends included
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeightLimits {
+    pub min: RawHeight,
+    pub max: RawHeight,
+}
+
+impl HeightLimits {
+    pub fn contains(&self, height: RawHeight) -> bool {
+        (self.min..=self.max).contains(&height)
+    }
+
+    /// Part of these limits which is also within `other` - `None` when they don't overlap
+    pub fn within(self, other: HeightLimits) -> Option<HeightLimits> {
+        let limits = HeightLimits {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        };
+        (limits.min <= limits.max).then_some(limits)
+    }
+}
+
+/// Height of the tabletop as the user sees it, in tenth mm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Height(i32);
+
+impl Height {
+    pub fn from_unit(value: f32, unit: HeightUnit) -> Self {
+        Height((value * unit.tenth_mm()).round() as i32)
+    }
+
+    pub fn in_unit(self, unit: HeightUnit) -> f32 {
+        self.0 as f32 / unit.tenth_mm()
+    }
+
+    /// Raw height the desk has to be at for this height - `None` when it's not something the desk can report
+    pub fn to_raw(self, height_offset: i16) -> Option<RawHeight> {
+        u16::try_from(self.0 - height_offset as i32)
+            .ok()
+            .map(RawHeight)
+    }
+
+    /// eg. `72.5 cm`
+    pub fn format(self, unit: HeightUnit) -> String {
+        format!("{:.1} {}", self.in_unit(unit), unit.symbol())
+    }
+}
+
+/// Calibration offset(in tenth mm) in the provided unit
+pub fn offset_in_unit(height_offset: i16, unit: HeightUnit) -> f32 {
+    height_offset as f32 / unit.tenth_mm()
+}
+
+/// Calibration offset in tenth mm - `None` when it's too big to be a tabletop
+pub fn offset_from_unit(value: f32, unit: HeightUnit) -> Option<i16> {
+    i16::try_from(Height::from_unit(value, unit).0).ok()
+}
+
+#[cfg(test)]
+mod height_suite {
+    use crate::height::{Height, HeightLimits, HeightUnit, RawHeight};
+
+    #[test]
+    fn should_convert_between_raw_and_calibrated_heights() {
+        let height = RawHeight(7000).calibrate(250);
+
+        assert_eq!(height.format(HeightUnit::Cm), "72.5 cm");
+        assert_eq!(height.format(HeightUnit::Inches), "28.5 in");
+        assert_eq!(
+            Height::from_unit(72.5, HeightUnit::Cm).to_raw(250),
+            Some(RawHeight(7000))
+        );
+        assert_eq!(Height::from_unit(1.0, HeightUnit::Cm).to_raw(250), None);
+    }
+
+    #[test]
+    fn should_narrow_limits() {
+        let hardware = HeightLimits {
+            min: RawHeight(6200),
+            max: RawHeight(12700),
+        };
+        let soft_limits = HeightLimits {
+            min: RawHeight(5000),
+            max: RawHeight(11000),
+        };
+
+        assert_eq!(
+            soft_limits.within(hardware),
+            Some(HeightLimits {
+                min: RawHeight(6200),
+                max: RawHeight(11000),
+            })
+        );
+        let above_desk = HeightLimits {
+            min: RawHeight(13000),
+            max: RawHeight(14000),
+        };
+        assert_eq!(above_desk.within(hardware), None);
+    }
+}
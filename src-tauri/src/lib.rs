@@ -0,0 +1,84 @@
+/*
+  Everything shared between the tray app(`main.rs`) and the command line interface(`bin/trayasen-cli.rs`)
+*/
+use tauri::{Window, WindowBuilder};
+use window_shadows::set_shadow;
+
+pub mod app_error;
+pub mod config_store;
+pub mod config_utils;
+pub mod config_watcher;
+pub mod desk_groups;
+pub mod desk_registry;
+pub mod desk_scanner;
+pub mod desk_supervisor;
+pub mod desk_transport;
+pub mod height;
+pub mod local_api;
+pub mod loose_idasen;
+pub mod movement_utils;
+#[cfg(feature = "mqtt")]
+pub mod mqtt_bridge;
+pub mod position_feed;
+pub mod preset_transfer;
+pub mod reminder_scheduler;
+pub mod shortcut_utils;
+#[cfg(test)]
+mod simulated_desk;
+pub mod tray_height;
+pub mod tray_utils;
+pub mod usage_history;
+
+// Whether a system should have custom decorations or not
+#[tauri::command]
+pub fn has_custom_decorations() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    false
+}
+
+pub trait WindowInitUtils {
+    /// Builds the window, or logs `err_msg` when it cannot be built(eg. a window with the same label is open already)
+    fn init_trayasen(self, title: &str, err_msg: &str, init_script: Option<&str>)
+        -> Option<Window>;
+}
+
+impl WindowInitUtils for WindowBuilder<'_> {
+    fn init_trayasen(
+        self,
+        title: &str,
+        err_msg: &str,
+        init_script: Option<&str>,
+    ) -> Option<Window> {
+        // We want to replace borders only on windows, as on macOS they are pretty enough, and on Linux it's not supported by `window_shadows`
+        let mut window_builder = if has_custom_decorations() {
+            self.inner_size(1280.0, 720.0)
+                .title(title)
+                .always_on_top(true)
+                .decorations(false)
+        } else {
+            self.inner_size(1280.0, 720.0)
+                .title(title)
+                .always_on_top(true)
+        };
+
+        if let Some(init_script) = init_script {
+            window_builder = window_builder.initialization_script(init_script);
+        }
+
+        let window_instance = match window_builder.build() {
+            Ok(window_instance) => window_instance,
+            Err(e) => {
+                log::error!("{}: {}", err_msg, e);
+                return None;
+            }
+        };
+        if has_custom_decorations() {
+            if let Err(e) = set_shadow(&window_instance, true) {
+                log::warn!("Error while setting the window shadow: {}", e);
+            }
+        }
+        Some(window_instance)
+    }
+}
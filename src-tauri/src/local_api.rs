@@ -0,0 +1,338 @@
+/*
+  Small local API of the running tray app. The tray app owns the bluetooth connection to the desk,
+  so scripts, editor plugins and `trayasen-cli` go through it instead of fighting over the desk.
+
+  The API listens on 127.0.0.1 on a random port. Every line sent to it is a JSON request, and is answered with a single JSON line:
+    -> {"token": "<token>", "command": "move_to", "height": 11000}
+    <- {"status": "ok", "data": {"height": 11004}}
+  The port and the token are written to `idasen-tray-api.json` next to the config file, readable only by the current user.
+*/
+use std::{fs, io::Write, time::Duration};
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{from_str, json, to_string};
+use tauri::AppHandle;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use uuid::Uuid;
+
+use crate::{
+    config_store, config_utils, desk_groups, desk_registry, height::RawHeight, loose_idasen,
+    movement_utils,
+};
+
+static API_INFO_FILE_NAME: &str = "idasen-tray-api.json";
+
+/// How long a client waits for the tray app to pick up
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+/// Longest request line(in bytes) the API accepts - real requests are far below it
+const MAX_REQUEST_LENGTH: u64 = 4096;
+
+/// Where to find the API of the running app
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ApiInfo {
+    pub port: u16,
+    pub token: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ApiCommand {
+    Height,
+    /// Height in tenth mm
+    MoveTo {
+        height: RawHeight,
+    },
+    MoveToPreset {
+        name: String,
+    },
+    Stop,
+    ListPresets,
+    /// Moves every desk of the group, answers with how each of them did
+    MoveGroupToPreset {
+        group: String,
+        name: String,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ApiRequest {
+    pub token: String,
+    #[serde(flatten)]
+    pub command: ApiCommand,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ApiResponse {
+    Ok { data: serde_json::Value },
+    Error { message: String },
+}
+
+fn get_api_info_path() -> String {
+    config_utils::get_data_file_path(API_INFO_FILE_NAME)
+}
+
+fn save_api_info(api_info: &ApiInfo) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // The token is as good as a password - the file is never readable by other users, not even for a moment
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut api_info_file = options.open(get_api_info_path())?;
+    // Mode only applies to new files - one left behind by an older version keeps its permissions otherwise
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        api_info_file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    api_info_file.write_all(to_string(api_info)?.as_bytes())
+}
+
+/// Start serving the API in the background
+pub fn spawn_local_api(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let listener = match TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Error while starting the local API: {}", e);
+                return;
+            }
+        };
+        let api_info = ApiInfo {
+            port: listener.local_addr().map(|addr| addr.port()).unwrap_or(0),
+            token: Uuid::new_v4().simple().to_string(),
+        };
+        if let Err(e) = save_api_info(&api_info) {
+            log::error!("Error while saving the local API info: {}", e);
+            return;
+        }
+        log::info!("Local API listening on port {}", api_info.port);
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let app_handle = app_handle.clone();
+                    let token = api_info.token.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = handle_connection(&app_handle, stream, &token).await {
+                            log::warn!("Local API connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Error while accepting a local API connection: {}", e),
+            }
+        }
+    });
+}
+
+async fn handle_connection(
+    app_handle: &AppHandle,
+    stream: TcpStream,
+    token: &str,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    while let Some(line) = read_request_line(&mut reader).await? {
+        let response = match from_str::<ApiRequest>(&line) {
+            Ok(request) if request.token != token => ApiResponse::Error {
+                message: "Invalid token.".to_string(),
+            },
+            Ok(request) => match handle_command(app_handle, request.command).await {
+                Ok(data) => ApiResponse::Ok { data },
+                Err(message) => ApiResponse::Error { message },
+            },
+            Err(e) => ApiResponse::Error {
+                message: format!("Invalid request: {}", e),
+            },
+        };
+
+        let mut response = to_string(&response)?;
+        response.push('\n');
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Next line sent by the client, `None` once it's done. Fails on lines longer than `MAX_REQUEST_LENGTH`,
+/// so that a client cannot make us buffer without limit.
+async fn read_request_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = reader
+        .take(MAX_REQUEST_LENGTH + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() as u64 > MAX_REQUEST_LENGTH {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Request is too long.",
+        ));
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Every command goes through the desk registry and the movement state, just like tray clicks
+async fn handle_command(
+    app_handle: &AppHandle,
+    command: ApiCommand,
+) -> Result<serde_json::Value, String> {
+    match command {
+        ApiCommand::Height => {
+            let desk =
+                desk_registry::get_active_desk(app_handle).ok_or("Desk is not connected.")?;
+            let position_speed = loose_idasen::get_position_and_speed(&desk)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(json!({ "height": position_speed.position, "speed": position_speed.speed }))
+        }
+        ApiCommand::MoveTo { height } => move_to(app_handle, height).await,
+        ApiCommand::MoveToPreset { name } => {
+            let config = config_store::get_config(app_handle);
+            let position = config
+                .saved_positions()
+                .iter()
+                .find(|pos| pos.name == name)
+                .ok_or(format!("No saved position named `{}`.", name))?;
+            move_to(app_handle, position.value).await
+        }
+        ApiCommand::Stop => {
+            movement_utils::stop(app_handle)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(json!({ "stopped": true }))
+        }
+        ApiCommand::ListPresets => {
+            Ok(json!(config_store::get_config(app_handle).saved_positions()))
+        }
+        ApiCommand::MoveGroupToPreset { group, name } => {
+            let results = desk_groups::move_group_to_preset(app_handle, &group, &name)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(json!(results))
+        }
+    }
+}
+
+async fn move_to(app_handle: &AppHandle, height: RawHeight) -> Result<serde_json::Value, String> {
+    desk_registry::get_active_desk(app_handle).ok_or("Desk is not connected.")?;
+    movement_utils::move_to_target(app_handle, height)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let desk = desk_registry::get_active_desk(app_handle).ok_or("Desk is not connected.")?;
+    let position = loose_idasen::get_position(&desk)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(json!({ "height": position }))
+}
+
+/// Send a command to the running tray app. Returns `None` if the app is not running.
+pub async fn send_command(command: ApiCommand) -> Option<Result<serde_json::Value, String>> {
+    let api_info = fs::read_to_string(get_api_info_path()).ok()?;
+    let api_info = from_str::<ApiInfo>(&api_info).ok()?;
+
+    // Info file stays behind when the app gets killed - if nobody listens, the app is not running
+    let stream = tokio::time::timeout(
+        CONNECT_TIMEOUT,
+        TcpStream::connect(("127.0.0.1", api_info.port)),
+    )
+    .await
+    .ok()?
+    .ok()?;
+
+    Some(send_request(stream, api_info.token, command).await)
+}
+
+async fn send_request(
+    stream: TcpStream,
+    token: String,
+    command: ApiCommand,
+) -> Result<serde_json::Value, String> {
+    let (reader, mut writer) = stream.into_split();
+    let mut request = to_string(&ApiRequest { token, command }).map_err(|e| e.to_string())?;
+    request.push('\n');
+    writer
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let response = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Trayasen closed the connection.")?;
+    match from_str::<ApiResponse>(&response).map_err(|e| e.to_string())? {
+        ApiResponse::Ok { data } => Ok(data),
+        ApiResponse::Error { message } => Err(message),
+    }
+}
+
+#[cfg(test)]
+mod protocol_suite {
+    use serde_json::{from_str, json, to_value};
+
+    use crate::{
+        height::RawHeight,
+        local_api::{read_request_line, ApiCommand, ApiRequest, MAX_REQUEST_LENGTH},
+    };
+
+    #[test]
+    fn should_parse_requests() {
+        let request =
+            from_str::<ApiRequest>(r#"{"token": "abc", "command": "move_to", "height": 11000}"#)
+                .unwrap();
+
+        assert_eq!(request.token, "abc");
+        assert!(matches!(
+            request.command,
+            ApiCommand::MoveTo {
+                height: RawHeight(11000)
+            }
+        ));
+    }
+
+    #[test]
+    fn should_serialize_commands_flat() {
+        let request = ApiRequest {
+            token: "abc".to_string(),
+            command: ApiCommand::MoveToPreset {
+                name: "Standing".to_string(),
+            },
+        };
+
+        assert_eq!(
+            to_value(&request).unwrap(),
+            json!({ "token": "abc", "command": "move_to_preset", "name": "Standing" })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_too_long_requests() {
+        let mut requests = "{}\n".as_bytes();
+        assert_eq!(
+            read_request_line(&mut requests).await.unwrap(),
+            Some("{}".to_string())
+        );
+        assert_eq!(read_request_line(&mut requests).await.unwrap(), None);
+
+        let too_long = "a".repeat(MAX_REQUEST_LENGTH as usize + 1);
+        let mut requests = too_long.as_bytes();
+        assert!(read_request_line(&mut requests).await.is_err());
+    }
+}
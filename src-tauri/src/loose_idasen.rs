@@ -2,50 +2,82 @@
   This file contains loose utils to interact with a desk bluetooth peripheral as if it's a desk.
   Requires the device in question to already be connected, otherwise it will error
 */
-use std::{
-    cmp::Ordering,
-    thread::sleep,
-    time::{Duration, Instant},
-};
+use std::time::Duration;
 
 use btleplug::{
     api::{
         BDAddr, Central, Characteristic, Manager as ApiManager, ParseBDAddrError,
-        Peripheral as ApiPeripheral, ScanFilter, WriteType,
+        Peripheral as ApiPeripheral, PeripheralProperties, ScanFilter, WriteType,
     },
     platform::{Adapter, Manager, Peripheral as PlatformPeripheral},
 };
+use futures::{future::join_all, StreamExt};
 use serde::Serialize;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::config_utils;
+use crate::{
+    app_error::AppError,
+    config_store,
+    config_utils::DeskProfile,
+    desk_transport::DeskTransport,
+    height::{HeightLimits, RawHeight},
+};
 
-const CONTROL_UUID: Uuid = Uuid::from_bytes([
+/// Service advertised by Idasen desks, holding the characteristics below
+pub(crate) const DESK_SERVICE_UUID: Uuid = Uuid::from_bytes([
+    0x99, 0xfa, 0x00, 0x01, 0x33, 0x8a, 0x10, 0x24, 0x8a, 0x49, 0x00, 0x9c, 0x02, 0x15, 0xf7, 0x8a,
+]);
+pub(crate) const CONTROL_UUID: Uuid = Uuid::from_bytes([
     0x99, 0xfa, 0x00, 0x02, 0x33, 0x8a, 0x10, 0x24, 0x8a, 0x49, 0x00, 0x9c, 0x02, 0x15, 0xf7, 0x8a,
 ]);
-const POSITION_UUID: Uuid = Uuid::from_bytes([
+pub(crate) const POSITION_UUID: Uuid = Uuid::from_bytes([
     0x99, 0xfa, 0x00, 0x21, 0x33, 0x8a, 0x10, 0x24, 0x8a, 0x49, 0x00, 0x9c, 0x02, 0x15, 0xf7, 0x8a,
 ]);
 
-const UP: [u8; 2] = [0x47, 0x00];
-const DOWN: [u8; 2] = [0x46, 0x00];
-const STOP: [u8; 2] = [0xFF, 0x00];
+pub(crate) const UP: [u8; 2] = [0x47, 0x00];
+pub(crate) const DOWN: [u8; 2] = [0x46, 0x00];
+pub(crate) const STOP: [u8; 2] = [0xFF, 0x00];
+
+pub const MIN_HEIGHT: RawHeight = RawHeight(6200);
+pub const MAX_HEIGHT: RawHeight = RawHeight(12700);
+/// Range the desk can physically move in - profiles can narrow it down with their own limits
+pub const HARDWARE_LIMITS: HeightLimits = HeightLimits {
+    min: MIN_HEIGHT,
+    max: MAX_HEIGHT,
+};
 
-pub const MIN_HEIGHT: u16 = 6200;
-pub const MAX_HEIGHT: u16 = 12700;
+/// How close(in tenth mm) to the target the desk has to stop for the move to be considered done
+pub const TARGET_TOLERANCE: i32 = 50;
+/// For how long the desk keeps moving after STOP is sent - we send it early to stop right at the target
+const BRAKING_TIME: Duration = Duration::from_millis(100);
+/// How often the move command is repeated while moving
+const COMMAND_REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+/// For how long we wait for the desk to halt after sending STOP
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(1);
+/// For how long the desk can report no speed without changing height before we consider it stuck
+const STALL_TIMEOUT: Duration = Duration::from_secs(1);
+/// For how long the height can stay the same before we consider the desk stuck, whatever the speed says
+const NO_PROGRESS_TIMEOUT: Duration = Duration::from_secs(3);
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PositionSpeed {
-    // tenth mm
-    pub position: u16,
-    // unknown
+    pub position: RawHeight,
+    // tenth mm per second, negative when going down
     pub speed: i16,
 }
 
-pub fn bytes_to_position_speed(bytes: &[u8]) -> PositionSpeed {
-    let position = u16::from_le_bytes([bytes[0], bytes[1]]) + MIN_HEIGHT;
-    let speed = i16::from_le_bytes([bytes[2], bytes[3]]);
-    PositionSpeed { position, speed }
+/// Decodes what the desk reports. `None` for garbled data - too short, or with a height no desk can have.
+pub fn bytes_to_position_speed(bytes: &[u8]) -> Option<PositionSpeed> {
+    let [position_low, position_high, speed_low, speed_high, ..] = *bytes else {
+        return None;
+    };
+    let position = u16::from_le_bytes([position_low, position_high]).checked_add(MIN_HEIGHT.0)?;
+    Some(PositionSpeed {
+        position: RawHeight(position),
+        speed: i16::from_le_bytes([speed_low, speed_high]),
+    })
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -62,15 +94,30 @@ pub enum BtError {
     #[error("Cannot find the device.")]
     CannotFindDevice,
 
+    #[error("No bluetooth adapter could scan: {}", join_adapter_errors(.0))]
+    AdaptersFailed(Vec<AdapterError>),
+
     #[error("Bluetooth characteristics not found: '{}'.", _0)]
     CharacteristicsNotFound(String),
 
-    #[error("Desired position has to be between MIN_HEIGHT and MAX_HEIGHT.")]
-    PositionNotInRange,
+    #[error("Desired position {target} is outside the height limits of the desk - it has to be between {} and {}(tenth mm).", .limits.min, .limits.max)]
+    PositionNotInRange {
+        target: RawHeight,
+        limits: HeightLimits,
+    },
 
     #[error("Cannot subscribe to read position.")]
     CannotSubscribePosition,
 
+    #[error("Desk reported a position which makes no sense: {0:?}.")]
+    InvalidPosition(Vec<u8>),
+
+    #[error("Movement was cancelled.")]
+    MovementCancelled,
+
+    #[error("Desk stopped moving before reaching the target - it might be obstructed.")]
+    MovementStalled,
+
     #[error("errored to parse mac address.")]
     MacAddrParseFailed(#[from] ParseBDAddrError),
 
@@ -78,242 +125,459 @@ pub enum BtError {
     BtlePlugError(#[from] btleplug::Error),
 }
 
+/// Desk which is connected and set up, along with the characteristics used to control it - found once, when connecting
+#[derive(Debug, Clone)]
 pub struct ConnectedBtDevice<T>
 where
-    T: ApiPeripheral,
+    T: DeskTransport,
 {
     pub mac_addr: BDAddr,
+    /// See `DeskTransport::peripheral_id`
+    pub peripheral_id: String,
     pub device_instance: T,
     pub control_characteristic: Characteristic,
     pub position_characteristic: Characteristic,
 }
 
+impl<T: DeskTransport> ConnectedBtDevice<T> {
+    /// See `known_address`
+    pub fn address(&self) -> Option<String> {
+        known_address(self.mac_addr)
+    }
+}
+
+/// Address of the desk as saved in the config - `None` on macOS, where it's hidden
+pub fn known_address(address: BDAddr) -> Option<String> {
+    (address != BDAddr::default()).then(|| address.to_string())
+}
+
+/// Desk as kept by the app
+pub type ConnectedDesk = ConnectedBtDevice<PlatformPeripheral>;
+
 /// Do a set of tasks for a peripheral to make the device(desk) usable.
-pub async fn setup_bt_desk_device(
-    device: &impl ApiPeripheral,
-) -> Result<ConnectedBtDevice<impl ApiPeripheral>, BtError> {
-    let mac_addr = BDAddr::default();
-    println!("got the mac! desk: {:?}", &device);
+pub async fn setup_bt_desk_device<T: DeskTransport>(
+    device: &T,
+) -> Result<ConnectedBtDevice<T>, BtError> {
+    // Not available on macOS - the peripheral id is what tells desks apart there
+    let mac_addr = device.address();
+    log::debug!("got the mac! desk: {:?}, address: {}", &device, mac_addr);
     let connection_result = device.connect().await;
-    match connection_result {
-        Err(conn_err) => {
-            println!("Error while connection: {}", conn_err);
-            return Err(BtError::UnknownConnect);
-        }
-        _ => {}
+    if let Err(conn_err) = connection_result {
+        log::error!("Error while connection: {}", conn_err);
+        return Err(BtError::UnknownConnect);
     }
-    println!("After connect...");
+    log::debug!("After connect...");
     let discover_result = device.discover_services().await;
-    match discover_result {
-        Err(disc_err) => {
-            println!("Error while discovering: {}", disc_err);
-            return Err(BtError::UnknownDiscover);
-        }
-        _ => {}
+    if let Err(disc_err) = discover_result {
+        log::error!("Error while discovering: {}", disc_err);
+        return Err(BtError::UnknownDiscover);
     }
-    println!("After service discover...");
+    log::debug!("After service discover...");
 
-    let control_characteristic = get_control_characteristic(device).await;
-    let position_characteristic = get_position_characteristic(device).await;
+    let control_characteristic = get_control_characteristic(device)?;
+    let position_characteristic = get_position_characteristic(device)?;
 
     if device.subscribe(&position_characteristic).await.is_err() {
-        println!("Error while subscribing...");
+        log::error!("Error while subscribing...");
         return Err(BtError::CannotSubscribePosition);
     };
-    println!("Desk is fully set up");
+    log::info!("Desk is fully set up");
 
     Ok(ConnectedBtDevice {
         device_instance: device.to_owned(),
         mac_addr,
+        peripheral_id: device.peripheral_id(),
         control_characteristic,
         position_characteristic,
     })
 }
 
-async fn get_list_of_desks_once(
-    loc_name: &Option<String>,
-) -> Result<Vec<ExpandedPeripheral>, BtError> {
-    match loc_name {
-        // If local name was provided
-        Some(loc_name) => {
-            let desks = get_desks(Some(loc_name.clone())).await;
-            desks
-        }
-        // If local name was NOT provided
-        None => {
-            let desks = get_desks(None).await;
-            desks
-        }
-    }
-}
-
+/// Desks found by the adapters, with the name provided(if any). Tries 3 times before giving up with the last error.
 pub async fn get_list_of_desks(
     loc_name: &Option<String>,
+    preferred_adapter: &Option<String>,
 ) -> Result<Vec<ExpandedPeripheral>, BtError> {
-    // try 3 times before erroring
-    let mut desks;
+    let mut last_error = BtError::CannotFindDevice;
     for _loop_iter in 0..3 {
-        desks = get_list_of_desks_once(loc_name).await;
-
-        if desks.is_ok() {
-            return desks;
-            // break;
+        match get_desks(loc_name.clone(), preferred_adapter).await {
+            Ok(desks) => return Ok(desks),
+            Err(e) => {
+                log::debug!("Error while looking for desks: {}", e);
+                last_error = e;
+            }
         }
     }
 
-    Err(BtError::CannotFindDevice)
+    Err(last_error)
 }
 
-// Getting characteristics every time is wasteful
-// TODO: Try to refactor this - maybe chuck this into shared tauri state?
-pub async fn get_control_characteristic(desk: &impl ApiPeripheral) -> Characteristic {
+// Only looked up when connecting - `ConnectedBtDevice` keeps them afterwards
+fn get_control_characteristic(desk: &impl DeskTransport) -> Result<Characteristic, BtError> {
     desk.characteristics()
-        .iter()
+        .into_iter()
         .find(|c| c.uuid == CONTROL_UUID)
         .ok_or_else(|| BtError::CharacteristicsNotFound("Control".to_string()))
-        .expect("err while getting characteristic")
-        .clone()
 }
 
-pub async fn get_position_characteristic(desk: &impl ApiPeripheral) -> Characteristic {
+fn get_position_characteristic(desk: &impl DeskTransport) -> Result<Characteristic, BtError> {
     desk.characteristics()
-        .iter()
+        .into_iter()
         .find(|c| c.uuid == POSITION_UUID)
         .ok_or_else(|| BtError::CharacteristicsNotFound("Position".to_string()))
-        .expect("Error while getting position characteristic")
-        .clone()
 }
 
-async fn up(desk: &impl ApiPeripheral) -> btleplug::Result<()> {
-    let control_characteristic = get_control_characteristic(desk).await;
-
-    desk.write(&control_characteristic, &UP, WriteType::WithoutResponse)
+async fn up(desk: &ConnectedBtDevice<impl DeskTransport>) -> btleplug::Result<()> {
+    desk.device_instance
+        .write(
+            &desk.control_characteristic,
+            &UP,
+            WriteType::WithoutResponse,
+        )
         .await
 }
 
-async fn down(desk: &impl ApiPeripheral) -> btleplug::Result<()> {
-    let control_characteristic = get_control_characteristic(desk).await;
-    desk.write(&control_characteristic, &DOWN, WriteType::WithoutResponse)
+async fn down(desk: &ConnectedBtDevice<impl DeskTransport>) -> btleplug::Result<()> {
+    desk.device_instance
+        .write(
+            &desk.control_characteristic,
+            &DOWN,
+            WriteType::WithoutResponse,
+        )
         .await
 }
 
-async fn stop(desk: &impl ApiPeripheral) -> btleplug::Result<()> {
-    let control_characteristic = get_control_characteristic(desk).await;
-    desk.write(&control_characteristic, &STOP, WriteType::WithoutResponse)
+pub async fn stop(desk: &ConnectedBtDevice<impl DeskTransport>) -> btleplug::Result<()> {
+    desk.device_instance
+        .write(
+            &desk.control_characteristic,
+            &STOP,
+            WriteType::WithoutResponse,
+        )
         .await
 }
 
+/// Fails with `BtError::PositionNotInRange` when the target is outside the limits
+pub fn check_in_limits(target: RawHeight, limits: HeightLimits) -> Result<RawHeight, BtError> {
+    if limits.contains(target) {
+        Ok(target)
+    } else {
+        Err(BtError::PositionNotInRange { target, limits })
+    }
+}
+
+/// Move the desk to the provided position, as long as it's within `limits`. Sends STOP and bails with `BtError::MovementCancelled` once `cancel_token` is cancelled.
 pub async fn move_to_target(
-    desk: &impl ApiPeripheral,
-    target_position: u16,
+    desk: &ConnectedBtDevice<impl DeskTransport>,
+    target_position: RawHeight,
+    limits: HeightLimits,
+    cancel_token: &CancellationToken,
 ) -> Result<(), BtError> {
-    println!("starting moving to target");
-    if !(MIN_HEIGHT..=MAX_HEIGHT).contains(&target_position) {
-        return Err(BtError::PositionNotInRange);
-    }
-
-    let mut position_reached = false;
-    let last_position = get_position(desk).await? as i16;
-    let last_position_read_at = Instant::now();
-    let target_position = target_position as i16;
-    while !position_reached {
-        sleep(Duration::from_millis(200));
-        let current_position = get_position(desk).await? as i16;
-        let going_up = match target_position.cmp(&current_position) {
-            Ordering::Greater => true,
-            Ordering::Less => false,
-            Ordering::Equal => return Ok(()),
-        };
-        let remaining_distance = (target_position - current_position).abs();
+    log::debug!("starting moving to target");
+    check_in_limits(target_position, limits)?;
+
+    // Subscribe before the first read, so that no notification gets lost in between
+    let mut notifications = desk.device_instance.notifications().await?;
+    let mut current = get_position_and_speed(desk).await?;
+    let target_position = target_position.0 as i32;
+    if (target_position - current.position.0 as i32).abs() <= TARGET_TOLERANCE {
+        return Ok(());
+    }
+    let going_up = target_position > current.position.0 as i32;
+
+    // The desk stops on its own if it does not get a move command for a while, so keep repeating it
+    let mut refresh = tokio::time::interval(COMMAND_REFRESH_INTERVAL);
+    let mut last_height_change = Instant::now();
+    // The desk reports no speed until its motor gets going, so that only counts as stalling once it has moved
+    let mut has_moved = false;
+    loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => {
+                log::info!("movement cancelled");
+                stop(desk).await?;
+                return Err(BtError::MovementCancelled);
+            }
+            _ = refresh.tick() => {
+                if is_stalled(&current, last_height_change.elapsed(), has_moved) {
+                    log::warn!("desk stalled at: {}", current.position);
+                    stop(desk).await?;
+                    return Err(BtError::MovementStalled);
+                }
+                if going_up {
+                    up(desk).await?;
+                } else {
+                    down(desk).await?;
+                }
+                continue;
+            }
+            notification = notifications.next() => match notification {
+                Some(notification) if notification.uuid == POSITION_UUID => {
+                    let Some(new_position) = bytes_to_position_speed(&notification.value) else {
+                        log::warn!("skipping garbled position: {:?}", notification.value);
+                        continue;
+                    };
+                    if new_position.position != current.position {
+                        last_height_change = Instant::now();
+                    }
+                    has_moved |= new_position.speed != 0 || new_position.position != current.position;
+                    current = new_position;
+                }
+                Some(_) => continue,
+                // Notification stream only ends when the desk is gone
+                None => return Err(BtError::BtlePlugError(btleplug::Error::NotConnected)),
+            }
+        }
 
-        println!(
-            "lastpos: {}, lastposreadat: {:?}, rem_dist: {}",
-            last_position, last_position_read_at, remaining_distance
+        // Distance left in the direction of travel - negative if we overshot
+        let remaining_distance = if going_up {
+            target_position - current.position.0 as i32
+        } else {
+            current.position.0 as i32 - target_position
+        };
+        let braking_distance = braking_distance(current.speed);
+        log::debug!(
+            "position: {}, speed: {}, rem_dist: {}, braking_dist: {}",
+            current.position,
+            current.speed,
+            remaining_distance,
+            braking_distance
         );
 
-        // If under/over 1cm we call it a day. From my testing it's under <3mm always(sometimes it might fuck up and do like 8mm but fuck it)
-        if remaining_distance <= 100 {
-            println!("position reached!");
-            position_reached = true;
+        if remaining_distance <= braking_distance {
             stop(desk).await?;
-        } else if going_up {
-            up(desk).await?;
-        } else if !going_up {
-            down(desk).await?;
+            break;
         }
     }
 
+    // Wait for the desk to come to a halt, so that the caller can rely on the final position
+    _ = tokio::time::timeout(SETTLE_TIMEOUT, async {
+        while let Some(notification) = notifications.next().await {
+            if notification.uuid == POSITION_UUID
+                && bytes_to_position_speed(&notification.value).is_some_and(|pos| pos.speed == 0)
+            {
+                break;
+            }
+        }
+    })
+    .await;
+    log::info!("position reached!");
+
     Ok(())
 }
 
-pub async fn get_position(desk: &impl ApiPeripheral) -> Result<u16, BtError> {
-    Ok(get_position_and_speed(desk).await?.position)
+/// Whether a desk that is supposed to be moving has stopped making progress
+fn is_stalled(current: &PositionSpeed, since_height_change: Duration, has_moved: bool) -> bool {
+    // No speed reported after moving means the desk gave up on its own, eg. after hitting something
+    (has_moved && current.speed == 0 && since_height_change >= STALL_TIMEOUT)
+        || since_height_change >= NO_PROGRESS_TIMEOUT
 }
 
-pub async fn get_position_and_speed(desk: &impl ApiPeripheral) -> Result<PositionSpeed, BtError> {
-    let position_characteristic = get_position_characteristic(desk).await;
+/// How far the desk travels after receiving STOP when going with the provided speed
+fn braking_distance(speed: i16) -> i32 {
+    (speed as i32).abs() * BRAKING_TIME.as_millis() as i32 / 1000
+}
 
-    let value = desk.read(&position_characteristic).await?;
-    Ok(bytes_to_position_speed(&value))
+pub async fn get_position(
+    desk: &ConnectedBtDevice<impl DeskTransport>,
+) -> Result<RawHeight, BtError> {
+    Ok(get_position_and_speed(desk).await?.position)
 }
 
-/// Peripheral expanded with it's name(we treat it as an ID)
+pub async fn get_position_and_speed(
+    desk: &ConnectedBtDevice<impl DeskTransport>,
+) -> Result<PositionSpeed, BtError> {
+    let value = desk
+        .device_instance
+        .read(&desk.position_characteristic)
+        .await?;
+    bytes_to_position_speed(&value).ok_or(BtError::InvalidPosition(value))
+}
+
+/// Peripheral expanded with it's name and id
 pub struct ExpandedPeripheral {
     pub perp: PlatformPeripheral,
     pub name: String,
+    /// See `DeskTransport::peripheral_id`
+    pub id: String,
+    /// See `known_address`
+    pub address: Option<String>,
+    /// Signal strength from the last advertisement, higher is closer
+    pub rssi: Option<i16>,
 }
 
-pub async fn get_desks(loc_name: Option<String>) -> Result<Vec<ExpandedPeripheral>, BtError> {
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
-    let mut jobs = Vec::new();
+/// Adapter which failed during a scan, eg. a USB dongle that got unplugged
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AdapterError {
+    pub adapter: String,
+    pub error: String,
+}
 
-    for adapter in adapters {
-        jobs.push(search_adapter_for_desks(adapter, loc_name.clone()).await);
+impl std::fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.adapter, self.error)
     }
+}
 
-    let mut desks = Vec::new();
-    for job in jobs {
-        desks.append(&mut job.unwrap());
+fn join_adapter_errors(adapter_errors: &[AdapterError]) -> String {
+    adapter_errors
+        .iter()
+        .map(|adapter_error| adapter_error.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Desks found by all the adapters, and what went wrong with the rest
+pub struct ScanResult {
+    pub desks: Vec<ExpandedPeripheral>,
+    pub adapter_errors: Vec<AdapterError>,
+}
+
+impl ScanResult {
+    /// The desks found. Without any, the adapter errors explain why - if there were none, no desk was around.
+    pub fn into_desks(self) -> Result<Vec<ExpandedPeripheral>, BtError> {
+        if !self.desks.is_empty() {
+            Ok(self.desks)
+        } else if self.adapter_errors.is_empty() {
+            Err(BtError::CannotFindDevice)
+        } else {
+            Err(BtError::AdaptersFailed(self.adapter_errors))
+        }
     }
+}
 
-    if desks.is_empty() {
-        Err(BtError::CannotFindDevice)
-    } else {
-        Ok(desks)
+pub async fn get_desks(
+    loc_name: Option<String>,
+    preferred_adapter: &Option<String>,
+) -> Result<Vec<ExpandedPeripheral>, BtError> {
+    let scan_result = scan_adapters(loc_name, preferred_adapter).await?;
+    for adapter_error in &scan_result.adapter_errors {
+        log::warn!("Error while scanning with adapter {}", adapter_error);
+    }
+    scan_result.into_desks()
+}
+
+/// Scans with every adapter at once - one adapter failing doesn't stop the others
+pub async fn scan_adapters(
+    loc_name: Option<String>,
+    preferred_adapter: &Option<String>,
+) -> Result<ScanResult, BtError> {
+    let adapters = get_adapters(preferred_adapter).await?;
+    let scans = adapters.into_iter().map(|(adapter_name, adapter)| {
+        let loc_name = loc_name.clone();
+        async move {
+            let desks = search_adapter_for_desks(adapter, loc_name).await;
+            (adapter_name, desks)
+        }
+    });
+
+    let mut scan_result = ScanResult {
+        desks: vec![],
+        adapter_errors: vec![],
+    };
+    for (adapter_name, desks) in join_all(scans).await {
+        match desks {
+            Ok(mut desks) => scan_result.desks.append(&mut desks),
+            Err(e) => scan_result.adapter_errors.push(AdapterError {
+                adapter: adapter_name,
+                error: e.to_string(),
+            }),
+        }
     }
+    Ok(scan_result)
+}
+
+/// Adapters to scan with, along with their names. Only the preferred adapter(`adapter` in the config) is used when there is one.
+pub async fn get_adapters(
+    preferred_adapter: &Option<String>,
+) -> Result<Vec<(String, Adapter)>, BtError> {
+    let manager = Manager::new().await?;
+    let mut adapters = Vec::new();
+    for adapter in manager.adapters().await? {
+        let adapter_name = adapter
+            .adapter_info()
+            .await
+            .unwrap_or_else(|_| "Unknown adapter".to_string());
+        adapters.push((adapter_name, adapter));
+    }
+
+    Ok(pick_adapters(adapters, preferred_adapter))
+}
+
+/// Only keeps the preferred adapter - matched by the start of its name, eg. `hci1`.
+/// Every adapter is kept when the preferred one is gone, so that the desk can still be found.
+pub fn pick_adapters<T>(
+    adapters: Vec<(String, T)>,
+    preferred_adapter: &Option<String>,
+) -> Vec<(String, T)> {
+    let Some(preferred_adapter) = preferred_adapter else {
+        return adapters;
+    };
+    if !adapters
+        .iter()
+        .any(|(name, _)| name.starts_with(preferred_adapter.as_str()))
+    {
+        log::warn!(
+            "Preferred adapter {} not found, using all adapters",
+            preferred_adapter
+        );
+        return adapters;
+    }
+    adapters
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(preferred_adapter.as_str()))
+        .collect()
 }
 
 async fn search_adapter_for_desks(
     adapter: Adapter,
     name: Option<String>,
 ) -> Result<Vec<ExpandedPeripheral>, BtError> {
-    adapter.start_scan(ScanFilter::default()).await?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![DESK_SERVICE_UUID],
+        })
+        .await?;
     tokio::time::sleep(Duration::from_secs(2)).await;
 
     let mut desks = Vec::new();
     for peripheral in adapter.peripherals().await? {
-        if let Some(props) = peripheral.properties().await? {
-            if match name {
-                Some(ref device_name) => {
-                    // We're matching by name - ideally we'd do this by MAC Address, but macOS doesn't expose MAC addresses of bluetooth devices
-                    // some devices might not have a local name - this might run into us into edge cases?
-                    let name = props.local_name.clone().unwrap_or("".to_string());
-
-                    device_name == &name
-                }
-                None => props.local_name.iter().any(|name| name.contains("")),
-            } {
-                desks.push(ExpandedPeripheral {
-                    perp: peripheral,
-                    name: props.local_name.unwrap_or("".to_string()),
-                });
-            }
+        if let Some(desk) = expand_if_desk(peripheral, &name).await? {
+            desks.push(desk);
         }
     }
     Ok(desks)
 }
 
+/// Expands the peripheral if it's a desk, with the provided name if any
+pub(crate) async fn expand_if_desk(
+    peripheral: PlatformPeripheral,
+    name: &Option<String>,
+) -> Result<Option<ExpandedPeripheral>, BtError> {
+    let desk = match peripheral.properties().await? {
+        Some(props) if is_matching_desk(&props, name) => Some(ExpandedPeripheral {
+            id: peripheral.peripheral_id(),
+            address: known_address(DeskTransport::address(&peripheral)),
+            perp: peripheral,
+            name: props.local_name.unwrap_or("".to_string()),
+            rssi: props.rssi,
+        }),
+        _ => None,
+    };
+    Ok(desk)
+}
+
+/// Whether the advertised device is a desk, with the provided name if any.
+/// Not every platform applies the scan filter(and adapters remember devices from earlier scans), so the services are checked again here.
+fn is_matching_desk(props: &PeripheralProperties, name: &Option<String>) -> bool {
+    if !props.services.contains(&DESK_SERVICE_UUID) {
+        return false;
+    }
+    match name {
+        // We're matching by name - saved desks are matched by their peripheral id afterwards(see `find_saved_desk`), since names are not unique
+        Some(device_name) => props.local_name.as_ref() == Some(device_name),
+        None => true,
+    }
+}
+
 enum SavedDeskStates {
     New,
     Saved,
@@ -329,84 +593,555 @@ impl SavedDeskStates {
 }
 
 /// A type of a potential candidate to be a desk - essentially just a bluetooth device
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct PotentialDesk {
     pub name: String,
+    /// See `DeskTransport::peripheral_id`
+    pub peripheral_id: String,
+    /// Signal strength, higher is closer - missing when the adapter does not report it
+    pub rssi: Option<i16>,
     pub status: String,
 }
+impl PotentialDesk {
+    pub fn new(desk: &ExpandedPeripheral, saved_desks: &[DeskProfile]) -> Self {
+        let is_saved = saved_desks
+            .iter()
+            .any(|saved_desk| match &saved_desk.peripheral_id {
+                Some(_) => saved_desk.is_same_desk(&desk.id, desk.address.as_deref()),
+                None => saved_desk.name == desk.name,
+            });
+        let status = if is_saved {
+            SavedDeskStates::Saved
+        } else {
+            SavedDeskStates::New
+        };
+        PotentialDesk {
+            name: desk.name.to_string(),
+            peripheral_id: desk.id.clone(),
+            rssi: desk.rssi,
+            status: status.as_str().to_string(),
+        }
+    }
+}
+
 // https://github.com/tauri-apps/tauri/issues/2533 - this has to be a Result
 /// Desk we're connecting to for UI info
 #[tauri::command]
-pub async fn get_available_desks_to_connect() -> Result<Vec<PotentialDesk>, String> {
-    let config = config_utils::get_or_create_config();
-    let desk_list = get_list_of_desks(&config.local_name).await;
+pub async fn get_available_desks_to_connect(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<PotentialDesk>, AppError> {
+    let config = config_store::get_config(&app_handle);
+    let saved_desks = config.saved_desks();
+    let desk_list = get_list_of_desks(&None, &config.adapter).await;
 
     match desk_list {
         Ok(desk_list) => {
             let desk_list_view = desk_list
                 .iter()
-                .map(|x| match config.local_name {
-                    Some(_) => PotentialDesk {
-                        name: x.name.to_string(),
-                        status: SavedDeskStates::Saved.as_str().to_string(),
-                    },
-                    None => PotentialDesk {
-                        name: x.name.to_string(),
-                        status: SavedDeskStates::New.as_str().to_string(),
-                    },
-                })
+                .map(|x| PotentialDesk::new(x, &saved_desks))
                 .collect::<Vec<PotentialDesk>>();
 
-            println!("Found desk list: {:?}", &desk_list_view);
+            log::debug!("Found desk list: {:?}", &desk_list_view);
 
-            return Ok(desk_list_view);
-        }
-        Err(e) => {
-            return Err(e.to_string());
+            Ok(desk_list_view)
         }
+        Err(e) => Err(e.into()),
     }
 }
 
-// TODO: UPDATE THE DESK INSTANCE MUTEX EVERY TIME YOU USE THIS FUNCTION HERE OTHERWISE IT WILL BREAK
+// TODO: UPDATE THE DESK REGISTRY EVERY TIME YOU USE THIS FUNCTION HERE OTHERWISE IT WILL BREAK
 // AS WE WILL HAVE DESYNC OF ACTUAL DESK AND CONNECTED ONE
-pub async fn connect_to_desk_by_name_internal(name: String) -> Result<PlatformPeripheral, BtError> {
-    let desk_to_connect = get_list_of_desks(&Some(name.clone())).await?;
+pub async fn connect_to_desk_by_name_internal(
+    name: String,
+    preferred_adapter: &Option<String>,
+) -> Result<ConnectedDesk, BtError> {
+    let desk_to_connect = get_list_of_desks(&Some(name.clone()), preferred_adapter).await?;
     let desk_to_connect = desk_to_connect
         .into_iter()
         .next()
-        .expect("Error while getting a desk to connect to");
-    let desk_to_connect = desk_to_connect.perp;
-    println!("after desk to connect!");
-
-    config_utils::save_local_name(name);
-    println!("saved desk!");
-    // TODO: try to use the ACTUAL connected bt device, instead of the pre-connected device instance
-    // Challenge here is that we cannot operate on `impl ApiPeripheral`, cause it's not sized.
-    // Maybe it should be boxed/arced?
-    let bt = setup_bt_desk_device(&desk_to_connect).await;
-
-    // if bt.is_err() {}
-
-    let _bt_ok = match bt {
-        Ok(val) => val,
-        Err(asd) => {
-            return Err(asd);
-        }
-    };
+        .ok_or(BtError::CannotFindDevice)?;
+    log::debug!("after desk to connect!");
+
+    setup_bt_desk_device(&desk_to_connect.perp).await
+}
+
+/// Which of the found desks(given as name, peripheral id and address) is the saved desk.
+/// Desks are told apart by their peripheral id or address, so that a neighbour's desk also called "Desk 1234" is never picked.
+/// The name is only a fallback for desks saved before their id was - skipping desks known to be another saved desk.
+pub fn find_saved_desk(
+    found_desks: &[(&str, &str, Option<&str>)],
+    saved_desk: &DeskProfile,
+    saved_desks: &[DeskProfile],
+) -> Option<usize> {
+    if saved_desk.peripheral_id.is_some() || saved_desk.address.is_some() {
+        return found_desks
+            .iter()
+            .position(|(_, id, address)| saved_desk.is_same_desk(id, *address));
+    }
+
+    found_desks.iter().position(|(name, id, address)| {
+        let is_other_desk = saved_desks
+            .iter()
+            .any(|desk| desk.is_same_desk(id, *address));
+        *name == saved_desk.name && !is_other_desk
+    })
+}
+
+/// Connects to whichever of the saved desks is nearby - the first one found, in the order of the provided desks.
+/// Returns the name of the desk it connected to.
+pub async fn connect_to_any_desk(
+    saved_desks: &[DeskProfile],
+    preferred_adapter: &Option<String>,
+) -> Result<(String, ConnectedDesk), BtError> {
+    let found_desks = get_list_of_desks(&None, preferred_adapter).await?;
+    let found_names_and_ids = found_desks
+        .iter()
+        .map(|desk| {
+            (
+                desk.name.as_str(),
+                desk.id.as_str(),
+                desk.address.as_deref(),
+            )
+        })
+        .collect::<Vec<(&str, &str, Option<&str>)>>();
+    let (saved_desk, desk_to_connect) = saved_desks
+        .iter()
+        .find_map(|saved_desk| {
+            let index = find_saved_desk(&found_names_and_ids, saved_desk, saved_desks)?;
+            Some((saved_desk, &found_desks[index]))
+        })
+        .ok_or(BtError::CannotFindDevice)?;
+
+    let desk = setup_bt_desk_device(&desk_to_connect.perp).await?;
+    Ok((saved_desk.name.clone(), desk))
+}
 
-    Ok(desk_to_connect)
+/// Same as `connect_to_any_desk`, for a single desk
+pub async fn connect_to_saved_desk(
+    saved_desk: &DeskProfile,
+    preferred_adapter: &Option<String>,
+) -> Result<ConnectedDesk, BtError> {
+    let (_, desk) =
+        connect_to_any_desk(std::slice::from_ref(saved_desk), preferred_adapter).await?;
+    Ok(desk)
 }
 
-// TODO: Figure out bluetooth mocking to improve testing; without mocks tests are impossible
 #[cfg(test)]
 mod connecting_suite {
     #[tokio::test]
     async fn should_fail_for_not_found_desk() {
-        let result =
-            crate::loose_idasen::connect_to_desk_by_name_internal("nonexistant_desk".to_string())
-                .await;
+        let result = crate::loose_idasen::connect_to_desk_by_name_internal(
+            "nonexistant_desk".to_string(),
+            &None,
+        )
+        .await;
         let err = result.unwrap_err();
 
         assert_eq!(err.to_string(), "Cannot find the device.");
     }
 }
+
+#[cfg(test)]
+mod setup_suite {
+    use crate::{
+        desk_transport::DeskTransport,
+        loose_idasen::{setup_bt_desk_device, BtError, CONTROL_UUID, POSITION_UUID},
+        simulated_desk::SimulatedDesk,
+    };
+
+    #[tokio::test]
+    async fn should_connect_and_subscribe_to_position() {
+        let desk = SimulatedDesk::new(7000);
+        let connected = setup_bt_desk_device(&desk).await.unwrap();
+
+        assert!(desk.is_connected().await.unwrap());
+        assert!(desk.is_subscribed());
+        assert_eq!(connected.control_characteristic.uuid, CONTROL_UUID);
+        assert_eq!(connected.position_characteristic.uuid, POSITION_UUID);
+    }
+
+    #[tokio::test]
+    async fn should_fail_for_unreachable_desk() {
+        let desk = SimulatedDesk::unreachable(7000);
+        let result = setup_bt_desk_device(&desk).await;
+
+        assert!(matches!(result, Err(BtError::UnknownConnect)));
+    }
+
+    #[tokio::test]
+    async fn should_fail_without_control_characteristic() {
+        let desk = SimulatedDesk::uncontrollable(7000);
+        let result = setup_bt_desk_device(&desk).await;
+
+        assert!(matches!(result, Err(BtError::CharacteristicsNotFound(_))));
+    }
+}
+
+#[cfg(test)]
+mod moving_suite {
+    use crate::{
+        height::{HeightLimits, RawHeight},
+        loose_idasen::{
+            bytes_to_position_speed, get_position, get_position_and_speed, move_to_target,
+            setup_bt_desk_device, BtError, ConnectedBtDevice, PositionSpeed, HARDWARE_LIMITS,
+            MAX_HEIGHT, MIN_HEIGHT, TARGET_TOLERANCE,
+        },
+        simulated_desk::SimulatedDesk,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    async fn connected_desk(height: u16) -> ConnectedBtDevice<SimulatedDesk> {
+        let desk = SimulatedDesk::new(height);
+        setup_bt_desk_device(&desk).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_read_position_and_speed() {
+        let desk = connected_desk(7350).await;
+
+        assert_eq!(
+            get_position_and_speed(&desk).await.unwrap(),
+            PositionSpeed {
+                position: RawHeight(7350),
+                speed: 0
+            }
+        );
+    }
+
+    #[test]
+    fn should_reject_garbled_positions() {
+        assert_eq!(
+            bytes_to_position_speed(&[0x34, 0x02, 0x7c, 0x01]),
+            Some(PositionSpeed {
+                position: RawHeight(MIN_HEIGHT.0 + 0x234),
+                speed: 0x17c
+            })
+        );
+        assert_eq!(bytes_to_position_speed(&[0x34, 0x02, 0x7c]), None);
+        assert_eq!(bytes_to_position_speed(&[]), None);
+        assert_eq!(bytes_to_position_speed(&[0xff, 0xff, 0x00, 0x00]), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_move_up_to_target() {
+        let desk = connected_desk(7000).await;
+        move_to_target(
+            &desk,
+            RawHeight(7500),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let position = get_position(&desk).await.unwrap().0 as i32;
+        assert!(
+            (position - 7500).abs() <= TARGET_TOLERANCE,
+            "stopped at {}",
+            position
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_move_down_to_target() {
+        let desk = connected_desk(8000).await;
+        move_to_target(
+            &desk,
+            RawHeight(7500),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let position = get_position(&desk).await.unwrap().0 as i32;
+        assert!(
+            (position - 7500).abs() <= TARGET_TOLERANCE,
+            "stopped at {}",
+            position
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_not_move_when_already_at_target() {
+        let desk = connected_desk(7520).await;
+        move_to_target(
+            &desk,
+            RawHeight(7500),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(desk.device_instance.height(), 7520);
+    }
+
+    #[tokio::test]
+    async fn should_reject_target_out_of_range() {
+        let desk = connected_desk(7000).await;
+        let result = move_to_target(
+            &desk,
+            RawHeight(MAX_HEIGHT.0 + 1),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(BtError::PositionNotInRange { .. })));
+        assert_eq!(desk.device_instance.height(), 7000);
+    }
+
+    #[tokio::test]
+    async fn should_reject_target_outside_soft_limits() {
+        let desk = connected_desk(7000).await;
+        let limits = HeightLimits {
+            min: RawHeight(6500),
+            max: RawHeight(10000),
+        };
+        let result =
+            move_to_target(&desk, RawHeight(11000), limits, &CancellationToken::new()).await;
+
+        assert!(matches!(
+            result,
+            Err(BtError::PositionNotInRange { target: RawHeight(11000), limits: error_limits }) if error_limits == limits
+        ));
+        assert_eq!(desk.device_instance.height(), 7000);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_fail_when_connection_drops() {
+        let desk = connected_desk(7000).await;
+        let dropping_desk = desk.device_instance.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+            dropping_desk.drop_connection();
+        });
+
+        let result = move_to_target(
+            &desk,
+            RawHeight(12000),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(BtError::BtlePlugError(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_stop_when_cancelled() {
+        let desk = connected_desk(7000).await;
+        let cancel_token = CancellationToken::new();
+        let cancelling_token = cancel_token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            cancelling_token.cancel();
+        });
+
+        let result = move_to_target(&desk, RawHeight(12000), HARDWARE_LIMITS, &cancel_token).await;
+        assert!(matches!(result, Err(BtError::MovementCancelled)));
+
+        // Give the desk time to brake, it should not move after that
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let stopped_at = desk.device_instance.height();
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(desk.device_instance.height(), stopped_at);
+        assert!(stopped_at < 7500, "stopped at {}", stopped_at);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_wait_for_slow_starting_desk() {
+        let desk = connected_desk(7000).await;
+        desk.device_instance
+            .delay_start(std::time::Duration::from_millis(1500));
+
+        move_to_target(
+            &desk,
+            RawHeight(7500),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        let position = get_position(&desk).await.unwrap().0 as i32;
+        assert!(
+            (position - 7500).abs() <= TARGET_TOLERANCE,
+            "stopped at {}",
+            position
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_stop_when_obstructed() {
+        let desk = connected_desk(7000).await;
+        desk.device_instance.obstruct_at(7300);
+
+        let result = move_to_target(
+            &desk,
+            RawHeight(8000),
+            HARDWARE_LIMITS,
+            &CancellationToken::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(BtError::MovementStalled)));
+        assert_eq!(desk.device_instance.height(), 7300);
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(desk.device_instance.speed(), 0);
+    }
+}
+
+#[cfg(test)]
+mod saved_desk_suite {
+    use crate::{config_utils::DeskProfile, loose_idasen::find_saved_desk};
+
+    fn saved_desk(name: &str, peripheral_id: Option<&str>) -> DeskProfile {
+        let mut desk = DeskProfile::new(name.to_string());
+        desk.peripheral_id = peripheral_id.map(str::to_string);
+        desk
+    }
+
+    #[test]
+    fn should_tell_apart_desks_with_the_same_name() {
+        let my_desk = saved_desk("Desk 1234", Some("my-id"));
+        let saved_desks = [my_desk.clone()];
+
+        let found_desks = [
+            ("Desk 1234", "neighbour-id", None),
+            ("Desk 1234", "my-id", None),
+        ];
+        assert_eq!(
+            find_saved_desk(&found_desks, &my_desk, &saved_desks),
+            Some(1)
+        );
+        assert_eq!(
+            find_saved_desk(&found_desks[..1], &my_desk, &saved_desks),
+            None
+        );
+    }
+
+    #[test]
+    fn should_fall_back_to_name_without_taking_other_desks() {
+        let old_desk = saved_desk("Desk 1234", None);
+        let other_desk = saved_desk("Desk 5678", Some("other-id"));
+        let saved_desks = [old_desk.clone(), other_desk];
+
+        let found_desks = [
+            ("Desk 1234", "other-id", None),
+            ("Desk 1234", "old-id", None),
+        ];
+        assert_eq!(
+            find_saved_desk(&found_desks, &old_desk, &saved_desks),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn should_find_desk_through_another_adapter() {
+        let mut my_desk = saved_desk("Desk 1234", Some("hci1/dev_C2_6D_88_A4_2B_01"));
+        my_desk.address = Some("C2:6D:88:A4:2B:01".to_string());
+        let saved_desks = [my_desk.clone()];
+
+        let found_desks = [
+            (
+                "Desk 1234",
+                "hci0/dev_E8_5B_5B_24_22_E4",
+                Some("E8:5B:5B:24:22:E4"),
+            ),
+            (
+                "Desk 1234",
+                "hci0/dev_C2_6D_88_A4_2B_01",
+                Some("C2:6D:88:A4:2B:01"),
+            ),
+        ];
+        assert_eq!(
+            find_saved_desk(&found_desks, &my_desk, &saved_desks),
+            Some(1)
+        );
+        assert_eq!(
+            find_saved_desk(&found_desks[..1], &my_desk, &saved_desks),
+            None
+        );
+
+        // Saved before addresses were, with the address only in the id
+        my_desk.address = None;
+        assert_eq!(
+            find_saved_desk(&found_desks, &my_desk, &[my_desk.clone()]),
+            Some(1)
+        );
+    }
+}
+
+#[cfg(test)]
+mod scanning_suite {
+    use btleplug::api::PeripheralProperties;
+
+    use crate::loose_idasen::{
+        is_matching_desk, pick_adapters, AdapterError, BtError, ScanResult, DESK_SERVICE_UUID,
+    };
+
+    fn advertisement(name: &str, services: Vec<uuid::Uuid>) -> PeripheralProperties {
+        PeripheralProperties {
+            local_name: Some(name.to_string()),
+            services,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_skip_devices_without_desk_service() {
+        let headphones = advertisement("Desk Headphones", vec![]);
+        let desk = advertisement("Desk 1234", vec![DESK_SERVICE_UUID]);
+
+        assert!(!is_matching_desk(&headphones, &None));
+        assert!(is_matching_desk(&desk, &None));
+        assert!(is_matching_desk(&desk, &Some("Desk 1234".to_string())));
+        assert!(!is_matching_desk(&desk, &Some("Desk 5678".to_string())));
+    }
+
+    #[test]
+    fn should_only_use_preferred_adapter_when_present() {
+        let adapters = vec![
+            ("hci0 (usb:v1D6Bp0246d0540)".to_string(), 0),
+            ("hci1 (usb:v0A12p0001d8891)".to_string(), 1),
+        ];
+
+        let picked = pick_adapters(adapters.clone(), &Some("hci1".to_string()));
+        assert_eq!(picked, vec![adapters[1].clone()]);
+
+        let picked = pick_adapters(adapters.clone(), &Some("hci2".to_string()));
+        assert_eq!(picked, adapters);
+    }
+
+    #[test]
+    fn should_not_find_desk_without_adapters() {
+        let scan_result = ScanResult {
+            desks: vec![],
+            adapter_errors: vec![],
+        };
+
+        assert!(matches!(
+            scan_result.into_desks(),
+            Err(BtError::CannotFindDevice)
+        ));
+    }
+
+    #[test]
+    fn should_report_failed_adapters_when_no_desk_found() {
+        let adapter_errors = vec![AdapterError {
+            adapter: "hci1 (usb:v0A12p0001d8891)".to_string(),
+            error: "Device or resource busy".to_string(),
+        }];
+        let scan_result = ScanResult {
+            desks: vec![],
+            adapter_errors: adapter_errors.clone(),
+        };
+
+        assert!(matches!(
+            scan_result.into_desks(),
+            Err(BtError::AdaptersFailed(errors)) if errors == adapter_errors
+        ));
+    }
+}
@@ -4,140 +4,114 @@
 )]
 
 use std::sync::Mutex;
-use loose_idasen::BtError;
 use tauri_plugin_autostart::MacosLauncher;
 
-use btleplug::platform::Peripheral as PlatformPeripheral;
-use tauri::{GlobalShortcutManager, Window, WindowBuilder};
 use tauri::{async_runtime::block_on, Manager, SystemTray, SystemTrayEvent};
+use trayasen::{
+    app_error::AppError, config_store, config_utils, config_utils::DeskProfile, config_watcher,
+    desk_groups, desk_registry, desk_scanner, desk_supervisor, local_api, loose_idasen,
+    loose_idasen::BtError, movement_utils, position_feed, preset_transfer, reminder_scheduler,
+    shortcut_utils, tray_height, tray_utils, usage_history, WindowInitUtils,
+};
 use window_shadows::set_shadow;
 
-mod desk_mutex;
-mod config_utils;
-mod loose_idasen;
-mod tray_utils;
-
-pub struct TauriSharedDesk(Mutex<Result<PlatformPeripheral, BtError>>);
-
-// Whether a system should have custom decorations or not
-#[tauri::command]
-fn has_custom_decorations() -> bool {
-    if cfg!(windows) {
-        return true;
-    }
-    false
-}
-
-pub trait WindowInitUtils {
-    fn init_trayasen(self, title: &str, err_msg: &str, init_script: Option<&str>) -> Window;
-} 
-
-impl WindowInitUtils for WindowBuilder<'_> {
-    fn init_trayasen(self, title: &str, err_msg: &str, init_script: Option<&str>) -> Window {
-        // We want to replace borders only on windows, as on macOS they are pretty enough, and on Linux it's not supported by `window_shadows`
-        let mut window_builder = if has_custom_decorations() {
-            self.inner_size(1280.0, 720.0).title(title).always_on_top(true).decorations(false)
-        } else {
-            self.inner_size(1280.0, 720.0).title(title).always_on_top(true)
-        };
-
-        if let Some(init_script) = init_script {
-            window_builder = window_builder.initialization_script(init_script);
-        }
-
-        let window_instance= window_builder.build().expect(err_msg);
-        if has_custom_decorations() {
-            set_shadow(&window_instance, true).unwrap();
-        }
-        window_instance
-    }
-}
-
-
 #[tauri::command]
 fn create_new_elem(
     app_handle: tauri::AppHandle,
     name: &str,
-    value: u16,
+    // In the unit the user picked
+    value: f32,
     shortcutvalue: Option<String>,
-) -> String {
-    let mut config = config_utils::get_config();
-    let mut shortcut_manager = app_handle.global_shortcut_manager();
-
-    println!("shortcut_acc: {:?}", shortcutvalue);
+) -> Result<String, AppError> {
+    log::debug!("shortcut_acc: {:?}", shortcutvalue);
+    let value = config_store::get_config(&app_handle).raw_height_in_limits(value)?;
 
-    let is_duplicate = config.saved_positions.iter().find(|elem| elem.name == name);
-    match is_duplicate {
-        Some(_) => {
-            // Duplicate found
-            "duplicate".to_string()
-        }
-        None => {
-            // No duplicate
-            config.saved_positions.push(config_utils::Position {
+    // Shortcut gets registered by the config store
+    let is_added = config_store::update_config(&app_handle, |config| {
+        let saved_positions = config.saved_positions_mut();
+        let is_duplicate = saved_positions.iter().any(|elem| elem.name == name);
+        if !is_duplicate {
+            saved_positions.push(config_utils::Position {
                 name: name.to_string(),
                 value,
-                shortcut: shortcutvalue.clone(),
+                shortcut: shortcutvalue.filter(|shortcut| !shortcut.is_empty()),
             });
-            config_utils::update_config(&config);
-
-            let desk = desk_mutex::get_desk_from_app_state(&app_handle);
-
-            let cloned_desk = desk.clone();
-            if let Some(shortcut_acc) = shortcutvalue {
-                if shortcut_acc != "" {
-                    _ = shortcut_manager.register(shortcut_acc.as_str(), move || {
-                        block_on(async {
-                            loose_idasen::move_to_target(&cloned_desk, value)
-                                .await
-                                .unwrap();
-                        });
-                    });
-                }
-            }
-
-            "success".to_string()
         }
+        !is_duplicate
+    })?;
+
+    if !is_added {
+        // Duplicate found
+        return Ok("duplicate".to_string());
     }
+
+    Ok("success".to_string())
 }
 
-/// Provided a name, will connect to a desk with this name - after this step, desk actually becomes usable
+/// Provided a name, will connect to a desk with this name - after this step, desk actually becomes usable.
+/// The peripheral id picks the right desk when several share the name.
 #[tauri::command]
-async fn connect_to_desk_by_name(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
-    println!("connecting to desk with name: {}", name);
-    let instantiated_desk = app_handle.state::<TauriSharedDesk>();
-    println!("with desk!...");
-    let cached_desk = loose_idasen::connect_to_desk_by_name_internal(name).await;
-    println!("after cached desk...");
-    if cached_desk.is_err() {
-        println!("in error!...");
-        return Err(cached_desk.unwrap_err().to_string());
-    }
-
-    println!("cached desk: some:{}, none:{}", cached_desk.is_ok(), cached_desk.is_err());
-    desk_mutex::assign_desk_to_mutex(&instantiated_desk, cached_desk);
-    println!("Successfuly connected to desk from frontend");
+async fn connect_to_desk_by_name(
+    app_handle: tauri::AppHandle,
+    name: String,
+    peripheral_id: Option<String>,
+) -> Result<(), AppError> {
+    log::info!("connecting to desk with name: {}", name);
+    let preferred_adapter = config_store::get_config(&app_handle).adapter;
+    let cached_desk = match peripheral_id {
+        Some(peripheral_id) => {
+            let mut desk = DeskProfile::new(name.clone());
+            desk.peripheral_id = Some(peripheral_id);
+            loose_idasen::connect_to_saved_desk(&desk, &preferred_adapter).await
+        }
+        None => {
+            loose_idasen::connect_to_desk_by_name_internal(name.clone(), &preferred_adapter).await
+        }
+    }?;
+    log::debug!("after cached desk...");
+    let peripheral_id = cached_desk.peripheral_id.clone();
+    let address = cached_desk.address();
+    desk_registry::insert_desk(&app_handle, name.clone(), cached_desk);
+    config_store::update_config(&app_handle, |config| {
+        config.select_desk(&name, Some(peripheral_id), address)
+    })?;
+    desk_supervisor::set_connection_state(&app_handle, desk_supervisor::ConnectionState::Connected);
+    log::info!("Successfuly connected to desk from frontend");
     Ok(())
 }
 
 fn main() {
-    let config = config_utils::get_or_create_config();
-    let initiated_desk = TauriSharedDesk(Mutex::new(Err(BtError::NotInitiated)));
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trayasen=debug"))
+        .init();
+
+    let (mut config, config_recovery) = config_utils::load_or_recover_config();
+    let connected_desks = desk_registry::TauriDeskRegistry::new();
+    let mut startup_connection: Result<(), BtError> = Err(BtError::NotInitiated);
 
     /*
     If there is a desk name present already, do not bother the end user with windows opening/loading. Just connect to his desk.
+    Any of the saved desks will do - eg. the office one when the laptop is at the office.
     */
-    let local_name = &config.local_name;
-    block_on(async {
-        if let Some(local_name) = local_name.clone() {
-            let cached_desk = loose_idasen::connect_to_desk_by_name_internal(local_name.clone())
-                .await;
-
-                desk_mutex::assign_desk_to_mutex(&initiated_desk, cached_desk);
-        }
-    });
+    if config.local_name.is_some() {
+        let connected_desk = block_on(loose_idasen::connect_to_any_desk(
+            &config.saved_desks(),
+            &config.adapter,
+        ));
+        startup_connection = connected_desk.map(|(desk_name, desk)| {
+            // The desk found becomes the one in use. Desks saved by older versions get their id and address saved here too.
+            let peripheral_id = desk.peripheral_id.clone();
+            let address = desk.address();
+            if !config.is_desk_in_use(&desk_name, &peripheral_id, address.as_deref()) {
+                config.select_desk(&desk_name, Some(peripheral_id), address);
+                if let Err(e) = config_utils::write_config(&config) {
+                    log::warn!("Error while saving the desk in use: {}", e);
+                }
+            }
+            connected_desks.insert(desk_name, desk);
+        });
+    }
 
-    println!("Loaded config: {:?}", config);
+    log::info!("Loaded config: {:?}", config);
 
     let tray_skeleton = config_utils::create_main_tray_menu(&config);
     let tray = SystemTray::new().with_menu(tray_skeleton);
@@ -149,71 +123,69 @@ fn main() {
         ))
         // Pass the tray instance to tauri to manage
         .system_tray(tray)
-        // Pass the desk instance to tauri to manage
-        .manage(initiated_desk)
-        // Pass the previously instantiates config. We ideally want to read fs only once.
-        .manage(config)
-        .setup(|app| {
+        // Pass the connected desks to tauri to manage
+        .manage(connected_desks)
+        // Movement in progress, so that it can be stopped or replaced
+        .manage(movement_utils::TauriMovementState::new())
+        // Supervisor updates it once it starts
+        .manage(desk_supervisor::TauriConnectionState(Mutex::new(
+            desk_supervisor::ConnectionState::Disconnected,
+        )))
+        // Latest position of the connected desk, fed by the supervisor
+        .manage(position_feed::TauriPositionFeed::new())
+        .manage(reminder_scheduler::TauriReminderState::new())
+        // Scan session of the setup window, so that it can be cancelled
+        .manage(desk_scanner::TauriScanState::new())
+        // Pass the previously instantiates config. We ideally want to read fs only once - every later change goes through the store.
+        .manage(config_store::TauriConfigStore::new(config))
+        .setup(move |app| {
             /*
                 On setup, we only wanna bail early if we're already connected
                 and register all the shortcuts
             */
-            let config = app.state::<config_utils::ConfigData>();
+            let config = config_store::get_config(&app.handle());
             let loc_name = &config.local_name;
 
+            if let Some(config_recovery) = config_recovery {
+                tray_utils::show_notification(&app.handle(), &config_recovery.to_string());
+            }
+
+            // Pick up changes made to the config file by hand
+            config_watcher::spawn_config_watcher(app.handle());
+            // Keep the desk connected for as long as the app runs
+            desk_supervisor::spawn_desk_supervisor(app.handle());
+            // Let scripts and the CLI drive the desk through the app
+            local_api::spawn_local_api(app.handle());
+            // Remind the user to switch between sitting and standing
+            reminder_scheduler::spawn_reminder_scheduler(app.handle());
+            // Keep track of the heights for usage statistics
+            usage_history::spawn_history_recorder(app.handle());
+            // Show the live height in the tray
+            tray_height::spawn_tray_height(app.handle());
+            #[cfg(feature = "mqtt")]
+            trayasen::mqtt_bridge::spawn_mqtt_bridge(app.handle());
+
             match loc_name {
                 Some(actual_loc_name) => {
-                    let desk_state = app.state::<TauriSharedDesk>();
-
-                    // We expect the desk to already exist at this point, since if loc_name exists, the first thing we do in the app is connect
-                    let desk = desk_state
-                        .0
-                        .lock()
-                        .expect("Error while unwrapping shared desk");
-                    let desk = desk.as_ref();
-                    match desk {
+                    // If loc_name exists, the first thing we do in the app is connect - so we already know how it went
+                    match &startup_connection {
                         /*
                             If the user is returning(has a config) immidiately close the window, not to eat resources
                             And then proceed to try to create the menu.
                         */
-                        Ok(desk) => {
-                            // Register all shortcuts
-                            let mut shortcut_manager = app.global_shortcut_manager();
-                            let all_positions = &config.saved_positions;
-                            let cloned_pos = all_positions.clone();
-
-                            // A lot of combinations do not not seem to be supported by tauri. Don't know for sure since there are no docs.
-                            for pos in cloned_pos.into_iter() {
-                                // Each iteration needs it's own clone; we do not want to consume the app state
-                                let cloned_desk = desk.clone();
-                                if let Some(shortcut_key) = &pos.shortcut {
-                                    if shortcut_key != "" {
-                                        _ = shortcut_manager.register(
-                                            shortcut_key.as_str(),
-                                            move || {
-                                                block_on(async {
-                                                    loose_idasen::move_to_target(
-                                                        &cloned_desk,
-                                                        pos.value,
-                                                    )
-                                                    .await
-                                                    .unwrap();
-                                                });
-                                            },
-                                        );
-                                    }
-                                }
-                            }
+                        Ok(_) => {
+                            shortcut_utils::register_shortcuts(&app.handle(), &config);
                         }
                         Err(e) => {
                             let err_window = tauri::WindowBuilder::new(app, "init_window", tauri::WindowUrl::App("index.html".into())).init_trayasen("Trayasen - Woops!","Error while creating window", None);
                             
                             // Open error window with the error
-                            println!("opening error window! error: {}", e);
+                            log::error!("opening error window! error: {}", e);
                             
                             // TODO: Passing state as a string literal to window via `eval` is a terrible way to handle state.
                             // This should be passed/handled via tauri state.
-                            _ = err_window.eval(
+                            if let Some(err_window) = err_window {
+                                _ = err_window.eval(
                                 format!(r#"
                                 window.stateWorkaround = {{
                                     title: "The app was not able to connect to your saved desk with name: `{}`.",
@@ -223,7 +195,8 @@ fn main() {
                                 }}
                                 history.replaceState({{}}, '','/error');
                         "#, actual_loc_name,actual_loc_name, e.to_string()).as_str(),
-                            );
+                                );
+                            }
                         }
                     }
                 }
@@ -231,13 +204,16 @@ fn main() {
                     let init_window = tauri::WindowBuilder::new(app, "main", tauri::WindowUrl::App("index.html".into())).init_trayasen("Trayasen - Setup", "Error while creating window", None);
                     
                     // If loc_name doesn't exist, that means there's no saved desk - meaning we need to show the initial setup window
-                    init_window
-                        .show()
-                        .expect("Error while trying to show the window");
+                    if let Some(init_window) = init_window {
+                        if let Err(e) = init_window.show() {
+                            log::error!("Error while trying to show the window: {}", e);
+                        }
 
-                    
-                    #[cfg(any(windows, target_os = "macos"))]
-                    set_shadow(&init_window, true).unwrap();
+                        #[cfg(any(windows, target_os = "macos"))]
+                        if let Err(e) = set_shadow(&init_window, true) {
+                            log::warn!("Error while setting the window shadow: {}", e);
+                        }
+                    }
                 }
             }
 
@@ -249,10 +225,22 @@ fn main() {
             config_utils::get_config,
             config_utils::remove_position,
             config_utils::remove_config,
+            config_utils::set_stop_shortcut,
             config_utils::reset_desk,
+            config_utils::get_height_settings,
+            config_utils::set_height_calibration,
+            config_utils::set_height_limits,
             loose_idasen::get_available_desks_to_connect,
+            desk_scanner::start_desk_scan,
+            desk_scanner::cancel_desk_scan,
             connect_to_desk_by_name,
-            has_custom_decorations
+            desk_supervisor::get_connection_state,
+            reminder_scheduler::get_posture_status,
+            usage_history::get_usage_stats,
+            preset_transfer::export_presets_to_path,
+            preset_transfer::import_presets_from_path,
+            desk_groups::move_group,
+            trayasen::has_custom_decorations
         ])
         .enable_macos_default_menu(false)
         // Register all the tray events, eg. clicks and stuff
@@ -264,44 +252,43 @@ fn main() {
                 config_utils::MANAGE_POSITIONS_ID => {
                     tray_utils::handle_manage_positions_menu_click(app)
                 }
+                config_utils::STOP_ID => movement_utils::spawn_stop(app),
+                config_utils::PAUSE_REMINDERS_ID => reminder_scheduler::toggle_paused(app),
+                switch_desk_id if switch_desk_id.starts_with(config_utils::SWITCH_DESK_ID_PREFIX) => {
+                    let desk_name = &switch_desk_id[config_utils::SWITCH_DESK_ID_PREFIX.len()..];
+                    desk_supervisor::switch_desk(app, desk_name);
+                }
                 // If event is not one of predefined, assume a position has been clicked
                 remaining_id => {
-                    // Get config one more time, in case there's a new position added since intialization
-                    let config = config_utils::get_config();
+                    let config = config_store::get_config(app);
+                    if let Some((group, position)) =
+                        config_utils::find_group_position(&config, remaining_id)
+                    {
+                        desk_groups::spawn_move_group_to_target(app, group.clone(), position.value);
+                        return;
+                    }
                     let updated_menus = config_utils::get_menu_items_from_config(&config);
-                    let found_elem = updated_menus
+                    // Position might have been removed since the menu was built
+                    match updated_menus
                         .iter()
                         .find(|pos| pos.position_elem.id_str == remaining_id)
-                        .expect("Clicked element not found");
-                    block_on(async {
-                        let desk = desk_mutex::get_desk_from_app_state(app);
-
-                        loose_idasen::move_to_target(&desk, found_elem.value)
-                            .await
-                            .unwrap();
-                    });
+                    {
+                        Some(found_elem) => {
+                            movement_utils::spawn_move_to_target(app, found_elem.value)
+                        }
+                        None => log::warn!("Clicked position not found: {}", remaining_id),
+                    }
                 }
             },
             _ => {}
         })
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
-        .run(move |app_handle, event| match event {
+        .run(move |_app_handle, event| match event {
             tauri::RunEvent::Ready => {}
-            /*
-                Exit requested, might mean that a new position has been added(or that just a window has been closed).
-                This is troublesome; since all the positions are actually system tray elements, we need to re-instantiate the entire tray
-                So, when we detected an exit requested, just to be safe, refresh the system tray.
-                TODO: We should probably have a way of checking for new elements, to remove redundant system tray refreshes
-            */
+            // Closing the last window should not close the app - it lives in the tray. Tray gets refreshed by the config store.
             tauri::RunEvent::ExitRequested { api, .. } => {
-                println!("Exit requested");
-                let config = config_utils::get_config();
-                let main_menu = config_utils::create_main_tray_menu(&config);
-                app_handle
-                    .tray_handle()
-                    .set_menu(main_menu)
-                    .expect("Error whilst unwrapping main menu");
+                log::debug!("Exit requested");
 
                 // Do not actually exit the app
                 api.prevent_exit();
@@ -0,0 +1,157 @@
+// Set of utils to start and interrupt desk movements from the tray, shortcuts and commands
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tauri::{AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    app_error, config_store, desk_registry,
+    height::RawHeight,
+    loose_idasen::{self, BtError, ConnectedDesk},
+    tray_utils,
+};
+
+/// Keeps track of the movements in progress, so that they can be interrupted by a new movement or by stopping the desks.
+/// Every desk moves on its own - all maps are keyed by the name of the desk.
+#[derive(Default)]
+pub struct TauriMovementState {
+    current_movements: Mutex<HashMap<String, CancellationToken>>,
+    // Held for the whole duration of a movement, so that two movements never write to the same desk at once
+    movement_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl TauriMovementState {
+    pub fn new() -> Self {
+        TauriMovementState {
+            current_movements: Mutex::new(HashMap::new()),
+            movement_locks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Cancel the movement of the desk in progress(if any) and hand out a token for the next one
+    fn replace_current(&self, desk_name: &str) -> CancellationToken {
+        let mut current_movements = self
+            .current_movements
+            .lock()
+            .expect("Error while unwrapping current movements");
+        if let Some(previous_movement) = current_movements.remove(desk_name) {
+            previous_movement.cancel();
+        }
+
+        let cancel_token = CancellationToken::new();
+        current_movements.insert(desk_name.to_string(), cancel_token.clone());
+        cancel_token
+    }
+
+    fn cancel_all(&self) {
+        let mut current_movements = self
+            .current_movements
+            .lock()
+            .expect("Error while unwrapping current movements");
+        for (_, previous_movement) in current_movements.drain() {
+            previous_movement.cancel();
+        }
+    }
+
+    fn movement_lock(&self, desk_name: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut movement_locks = self
+            .movement_locks
+            .lock()
+            .expect("Error while unwrapping movement locks");
+        movement_locks
+            .entry(desk_name.to_string())
+            .or_default()
+            .clone()
+    }
+}
+
+/// Move the desk in use, interrupting its movement in progress. Resolves once the desk arrives, or the movement gets replaced.
+pub async fn move_to_target(
+    app_handle: &AppHandle,
+    target_position: RawHeight,
+) -> Result<(), BtError> {
+    let local_name = config_store::get_config(app_handle)
+        .local_name
+        .ok_or(BtError::NotInitiated)?;
+    let desk = desk_registry::get_desk(app_handle, &local_name).ok_or(BtError::NotInitiated)?;
+    move_desk_to_target(app_handle, &local_name, &desk, target_position).await
+}
+
+/// Same as `move_to_target`, for any connected desk. Fails when the target is outside the height limits of the desk.
+pub async fn move_desk_to_target(
+    app_handle: &AppHandle,
+    desk_name: &str,
+    desk: &ConnectedDesk,
+    target_position: RawHeight,
+) -> Result<(), BtError> {
+    let movement_state = app_handle.state::<TauriMovementState>();
+    let cancel_token = movement_state.replace_current(desk_name);
+    // The previous movement sends STOP when cancelled - let it finish before we start writing
+    let movement_lock = movement_state.movement_lock(desk_name);
+    let _movement_guard = movement_lock.lock().await;
+    if cancel_token.is_cancelled() {
+        return Err(BtError::MovementCancelled);
+    }
+
+    let limits = config_store::get_config(app_handle).desk_height_limits(desk_name);
+    loose_idasen::move_to_target(desk, target_position, limits, &cancel_token).await
+}
+
+/// Interrupt the movements in progress and make sure every connected desk stands still
+pub async fn stop(app_handle: &AppHandle) -> Result<(), BtError> {
+    app_handle.state::<TauriMovementState>().cancel_all();
+
+    let mut result = Ok(());
+    for (desk_name, desk) in desk_registry::connected_desks(app_handle) {
+        if let Err(e) = loose_idasen::stop(&desk).await {
+            log::warn!("Error while stopping desk {}: {}", desk_name, e);
+            result = Err(BtError::from(e));
+        }
+    }
+    result
+}
+
+/// Same as `move_to_target`, but in the background.
+/// Tray and shortcut handlers run on the main thread, so they can't wait for the desk to arrive.
+pub fn spawn_move_to_target(app_handle: &AppHandle, target_position: RawHeight) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        match move_to_target(&app_handle, target_position).await {
+            Ok(()) | Err(BtError::MovementCancelled) => {}
+            Err(BtError::MovementStalled) => {
+                tray_utils::show_notification(
+                    &app_handle,
+                    "The desk stopped before reaching the position. Check if nothing is blocking it.",
+                );
+            }
+            // Positions saved before the limits were set might be past them
+            Err(BtError::PositionNotInRange { target, limits }) => {
+                let config = config_store::get_config(&app_handle);
+                tray_utils::show_notification(
+                    &app_handle,
+                    &format!(
+                        "The desk was not moved - {} is outside its height limits of {} to {}.",
+                        config.format_height(target),
+                        config.format_height(limits.min),
+                        config.format_height(limits.max)
+                    ),
+                );
+            }
+            Err(e) => app_error::report(&app_handle, "Error while moving the desk", &e.into()),
+        }
+    });
+}
+
+/// Same as `stop`, but in the background
+pub fn spawn_stop(app_handle: &AppHandle) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = stop(&app_handle).await {
+            app_error::report(&app_handle, "Error while stopping the desk", &e.into());
+        }
+    });
+}
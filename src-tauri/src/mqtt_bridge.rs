@@ -0,0 +1,661 @@
+/*
+  Publishes the desk to an MQTT broker, so that it can be controlled from Home Assistant.
+  Only built with the `mqtt` feature. Set up through the `mqtt` section of the config:
+    "mqtt": { "host": "192.168.1.10", "port": 1883, "username": "user", "password": "pass" }
+
+  Under `<base_topic>/<desk>`:
+    - `availability` - `online`/`offline`
+    - `height` - height in cm(with the calibration offset of the desk), `height/set` moves the desk to the height in cm
+    - `moving` - `ON`/`OFF`
+    - `preset/set` - moves the desk to the saved position with the name sent
+    - `stop/set` - stops the desk
+  Home Assistant discovery messages are published for all of them on every connection, and again whenever the saved positions change.
+  The bridge follows the desk in use - after switching desks, the previous one goes `offline` and the new one gets published.
+*/
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use serde_json::{json, Value};
+use tauri::AppHandle;
+use tokio::{sync::watch, time::Instant};
+
+use crate::{
+    config_store,
+    config_utils::{ConfigData, MqttConfig, Position},
+    height::{Height, HeightLimits, HeightUnit},
+    movement_utils, position_feed,
+};
+
+/// How often the height gets published while the desk is moving
+const MOVING_PUBLISH_INTERVAL: Duration = Duration::from_millis(500);
+/// How long we wait before polling the broker again after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long we wait for the broker to take the disconnect, when switching to another desk or broker
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq)]
+pub enum DeskCommand {
+    /// Height of the tabletop - Home Assistant always gets centimetres, it converts them for the user on its own
+    MoveTo(Height),
+    MoveToPreset(String),
+    Stop,
+}
+
+#[derive(Clone)]
+pub struct MqttTopics {
+    /// Identifier of the desk, safe to use in topics and Home Assistant ids
+    node_id: String,
+    base: String,
+    discovery_prefix: String,
+}
+
+impl MqttTopics {
+    pub fn new(config: &MqttConfig, desk_name: &str) -> Self {
+        let node_id = slugify(desk_name);
+        MqttTopics {
+            base: format!("{}/{}", config.base_topic, node_id),
+            node_id,
+            discovery_prefix: config.discovery_prefix.clone(),
+        }
+    }
+
+    pub fn availability(&self) -> String {
+        format!("{}/availability", self.base)
+    }
+
+    pub fn height_state(&self) -> String {
+        format!("{}/height", self.base)
+    }
+
+    pub fn height_command(&self) -> String {
+        format!("{}/height/set", self.base)
+    }
+
+    pub fn moving_state(&self) -> String {
+        format!("{}/moving", self.base)
+    }
+
+    pub fn preset_command(&self) -> String {
+        format!("{}/preset/set", self.base)
+    }
+
+    pub fn stop_command(&self) -> String {
+        format!("{}/stop/set", self.base)
+    }
+
+    fn discovery(&self, component: &str, object_id: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            self.discovery_prefix, component, self.node_id, object_id
+        )
+    }
+
+    fn preset_discovery(&self, position: &Position) -> String {
+        self.discovery("button", &preset_object_id(position))
+    }
+}
+
+fn preset_object_id(position: &Position) -> String {
+    format!("preset_{}", slugify(&position.name))
+}
+
+/// Lowercase name with everything but letters and digits replaced, eg. `Desk 1234` -> `desk_1234`
+fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Home Assistant discovery messages - a number for the height, a sensor for movement and a button per saved position
+pub fn discovery_messages(
+    topics: &MqttTopics,
+    desk_name: &str,
+    positions: &[Position],
+    height_offset: i16,
+    height_limits: HeightLimits,
+) -> Vec<(String, Value)> {
+    let device = json!({
+        "identifiers": [format!("trayasen_{}", topics.node_id)],
+        "name": desk_name,
+        "manufacturer": "IKEA",
+        "model": "Idasen",
+    });
+    let entity = |object_id: &str, name: &str| {
+        json!({
+            "name": name,
+            "unique_id": format!("trayasen_{}_{}", topics.node_id, object_id),
+            "availability_topic": topics.availability(),
+            "device": device,
+        })
+    };
+    let with = |mut entity: Value, fields: Value| {
+        if let (Some(entity), Some(fields)) = (entity.as_object_mut(), fields.as_object()) {
+            entity.extend(fields.clone());
+        }
+        entity
+    };
+
+    let mut messages = vec![
+        (
+            topics.discovery("number", "height"),
+            with(
+                entity("height", "Height"),
+                json!({
+                    "state_topic": topics.height_state(),
+                    "command_topic": topics.height_command(),
+                    "min": height_limits.min.calibrate(height_offset).in_unit(HeightUnit::Cm),
+                    "max": height_limits.max.calibrate(height_offset).in_unit(HeightUnit::Cm),
+                    "step": 0.5,
+                    "mode": "box",
+                    "unit_of_measurement": "cm",
+                }),
+            ),
+        ),
+        (
+            topics.discovery("binary_sensor", "moving"),
+            with(
+                entity("moving", "Moving"),
+                json!({
+                    "state_topic": topics.moving_state(),
+                    "device_class": "moving",
+                }),
+            ),
+        ),
+        (
+            topics.discovery("button", "stop"),
+            with(
+                entity("stop", "Stop"),
+                json!({ "command_topic": topics.stop_command() }),
+            ),
+        ),
+    ];
+
+    for position in positions {
+        let object_id = preset_object_id(position);
+        messages.push((
+            topics.preset_discovery(position),
+            with(
+                entity(&object_id, &position.name),
+                json!({
+                    "command_topic": topics.preset_command(),
+                    "payload_press": position.name,
+                }),
+            ),
+        ));
+    }
+
+    messages
+}
+
+pub fn parse_command(topics: &MqttTopics, topic: &str, payload: &[u8]) -> Option<DeskCommand> {
+    let payload = String::from_utf8_lossy(payload).trim().to_string();
+    if topic == topics.height_command() {
+        let cm = payload.parse::<f32>().ok()?;
+        Some(DeskCommand::MoveTo(Height::from_unit(cm, HeightUnit::Cm)))
+    } else if topic == topics.preset_command() {
+        Some(DeskCommand::MoveToPreset(payload))
+    } else if topic == topics.stop_command() {
+        Some(DeskCommand::Stop)
+    } else {
+        None
+    }
+}
+
+/// Publishes the discovery of the desk, and takes the buttons of `removed_positions` out of Home Assistant
+async fn publish_discovery(
+    client: &AsyncClient,
+    topics: &MqttTopics,
+    desk_name: &str,
+    discovery_state: &DiscoveryState,
+    removed_positions: &[Position],
+) -> Result<(), rumqttc::ClientError> {
+    let messages = discovery_messages(
+        topics,
+        desk_name,
+        &discovery_state.positions,
+        discovery_state.height_offset,
+        discovery_state.height_limits,
+    );
+    for (topic, payload) in messages {
+        client
+            .publish(topic, QoS::AtLeastOnce, true, payload.to_string())
+            .await?;
+    }
+    // Empty retained config removes the entity
+    for position in removed_positions {
+        client
+            .publish(
+                topics.preset_discovery(position),
+                QoS::AtLeastOnce,
+                true,
+                "",
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+pub fn create_client(
+    config: &MqttConfig,
+    topics: &MqttTopics,
+) -> (AsyncClient, rumqttc::EventLoop) {
+    let mut options = MqttOptions::new(
+        format!("trayasen-{}", topics.node_id),
+        config.host.clone(),
+        config.port,
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        topics.availability(),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        options.set_credentials(username.clone(), password.clone());
+    }
+    AsyncClient::new(options, 16)
+}
+
+/// Connects to the broker from the config(if there is one) and keeps the desk in use published for as long as the app runs.
+/// Switching to another desk, or changing the broker, starts over with the new one.
+pub fn spawn_mqtt_bridge(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut configs = config_store::subscribe(&app_handle);
+        loop {
+            let config = configs.borrow_and_update().clone();
+            let keep_going = match (config.mqtt, config.local_name) {
+                (Some(mqtt_config), Some(desk_name)) => {
+                    run_bridge(&app_handle, mqtt_config, desk_name, &mut configs).await
+                }
+                _ => configs.changed().await.is_ok(),
+            };
+            if !keep_going {
+                return;
+            }
+        }
+    });
+}
+
+/// Keeps the desk published until the broker or the desk in use changes. Returns `false` once the config is gone for good.
+async fn run_bridge(
+    app_handle: &AppHandle,
+    mqtt_config: MqttConfig,
+    desk_name: String,
+    configs: &mut watch::Receiver<ConfigData>,
+) -> bool {
+    let topics = MqttTopics::new(&mqtt_config, &desk_name);
+    let (client, mut event_loop) = create_client(&mqtt_config, &topics);
+    // Counts the connections to the broker, so that discovery knows when to publish everything again
+    let (connections, connection_count) = watch::channel(0u64);
+
+    let state_task = tauri::async_runtime::spawn({
+        let (app_handle, client, topics) = (app_handle.clone(), client.clone(), topics.clone());
+        async move { publish_state(&app_handle, &client, &topics).await }
+    });
+    let discovery_task = tauri::async_runtime::spawn({
+        let (app_handle, client, topics) = (app_handle.clone(), client.clone(), topics.clone());
+        let desk_name = desk_name.clone();
+        async move {
+            keep_discovery_published(&app_handle, &client, &topics, &desk_name, connection_count)
+                .await
+        }
+    });
+    let event_task = tauri::async_runtime::spawn({
+        let (app_handle, topics) = (app_handle.clone(), topics.clone());
+        let host = mqtt_config.host.clone();
+        async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        log::info!("Connected to MQTT broker: {}", host);
+                        connections.send_modify(|count| *count += 1);
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        if let Some(command) =
+                            parse_command(&topics, &publish.topic, &publish.payload)
+                        {
+                            log::info!("MQTT command: {:?}", command);
+                            run_command(&app_handle, command);
+                        }
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("MQTT connection error: {}", e);
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        }
+    });
+
+    let keep_going = loop {
+        if configs.changed().await.is_err() {
+            break false;
+        }
+        let config = configs.borrow_and_update();
+        if config.mqtt.as_ref() != Some(&mqtt_config)
+            || config.local_name.as_deref() != Some(desk_name.as_str())
+        {
+            break true;
+        }
+    };
+
+    log::info!("Unpublishing desk {} from MQTT", desk_name);
+    state_task.abort();
+    discovery_task.abort();
+    // The broker only sends the last will when the connection drops, not on a clean disconnect
+    _ = client
+        .publish(topics.availability(), QoS::AtLeastOnce, true, "offline")
+        .await;
+    _ = client.disconnect().await;
+    if tokio::time::timeout(DISCONNECT_TIMEOUT, event_task)
+        .await
+        .is_err()
+    {
+        log::warn!("MQTT broker did not take the disconnect in time");
+    }
+    keep_going
+}
+
+/// Everything the discovery messages are made of - they get published again when it changes
+#[derive(Debug, Clone, PartialEq)]
+struct DiscoveryState {
+    positions: Vec<Position>,
+    height_offset: i16,
+    height_limits: HeightLimits,
+}
+
+impl DiscoveryState {
+    fn new(config: &ConfigData) -> Self {
+        DiscoveryState {
+            positions: config.saved_positions().to_vec(),
+            height_offset: config.height_offset(),
+            height_limits: config.height_limits(),
+        }
+    }
+}
+
+/// Subscribes to the command topics and publishes discovery on every connection.
+/// Presets and heights changed while connected are published right away, removed presets are taken out of Home Assistant.
+async fn keep_discovery_published(
+    app_handle: &AppHandle,
+    client: &AsyncClient,
+    topics: &MqttTopics,
+    desk_name: &str,
+    mut connection_count: watch::Receiver<u64>,
+) {
+    let mut configs = config_store::subscribe(app_handle);
+    let mut published: Option<DiscoveryState> = None;
+    loop {
+        tokio::select! {
+            connected = connection_count.changed() => {
+                if connected.is_err() {
+                    return;
+                }
+                // Subscriptions and retained messages do not survive reconnecting with a clean session
+                for topic in [
+                    topics.height_command(),
+                    topics.preset_command(),
+                    topics.stop_command(),
+                ] {
+                    if let Err(e) = client.subscribe(topic, QoS::AtLeastOnce).await {
+                        log::error!("Error while subscribing to MQTT topics: {}", e);
+                    }
+                }
+                published = None;
+            }
+            changed = configs.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+        if *connection_count.borrow() == 0 {
+            continue;
+        }
+
+        let discovery_state = DiscoveryState::new(&configs.borrow_and_update());
+        if published.as_ref() == Some(&discovery_state) {
+            continue;
+        }
+        let removed_positions = published
+            .as_ref()
+            .map(|published| removed_positions(&published.positions, &discovery_state.positions))
+            .unwrap_or_default();
+        match publish_discovery(
+            client,
+            topics,
+            desk_name,
+            &discovery_state,
+            &removed_positions,
+        )
+        .await
+        {
+            Ok(()) => published = Some(discovery_state),
+            Err(e) => log::error!("Error while publishing MQTT discovery: {}", e),
+        }
+    }
+}
+
+/// Positions which are not saved anymore - renamed ones count as removed
+fn removed_positions(published: &[Position], saved: &[Position]) -> Vec<Position> {
+    published
+        .iter()
+        .filter(|published| !saved.iter().any(|saved| saved.name == published.name))
+        .cloned()
+        .collect()
+}
+
+fn run_command(app_handle: &AppHandle, command: DeskCommand) {
+    match command {
+        DeskCommand::MoveTo(height) => {
+            let height_offset = config_store::get_config(app_handle).height_offset();
+            match height.to_raw(height_offset) {
+                Some(height) => movement_utils::spawn_move_to_target(app_handle, height),
+                None => log::warn!("MQTT asked for a height out of range: {:?}", height),
+            }
+        }
+        DeskCommand::MoveToPreset(name) => {
+            let config = config_store::get_config(app_handle);
+            match config.saved_positions().iter().find(|pos| pos.name == name) {
+                Some(position) => movement_utils::spawn_move_to_target(app_handle, position.value),
+                None => log::warn!("MQTT asked for unknown position: {}", name),
+            }
+        }
+        DeskCommand::Stop => movement_utils::spawn_stop(app_handle),
+    }
+}
+
+/// Publishes height, movement and availability from the position feed
+async fn publish_state(app_handle: &AppHandle, client: &AsyncClient, topics: &MqttTopics) {
+    let mut positions = position_feed::subscribe(app_handle);
+    let mut last_height_publish: Option<Instant> = None;
+    let mut was_moving = None;
+    let mut was_available = None;
+
+    loop {
+        let position_speed = *positions.borrow_and_update();
+        let is_available = position_speed.is_some();
+        if was_available != Some(is_available) {
+            let availability = if is_available { "online" } else { "offline" };
+            _ = client
+                .publish(topics.availability(), QoS::AtLeastOnce, true, availability)
+                .await;
+            was_available = Some(is_available);
+        }
+
+        if let Some(position_speed) = position_speed {
+            let is_moving = position_speed.speed != 0;
+            if was_moving != Some(is_moving) {
+                let moving = if is_moving { "ON" } else { "OFF" };
+                _ = client
+                    .publish(topics.moving_state(), QoS::AtLeastOnce, true, moving)
+                    .await;
+                was_moving = Some(is_moving);
+            }
+
+            // While moving, the desk reports its height many times a second - no need to flood the broker
+            let throttled = is_moving
+                && last_height_publish
+                    .is_some_and(|published_at| published_at.elapsed() < MOVING_PUBLISH_INTERVAL);
+            if !throttled {
+                // Read every time, so that a new calibration shows up right away
+                let height_offset = config_store::get_config(app_handle).height_offset();
+                _ = client
+                    .publish(
+                        topics.height_state(),
+                        QoS::AtLeastOnce,
+                        true,
+                        format!(
+                            "{:.1}",
+                            position_speed
+                                .position
+                                .calibrate(height_offset)
+                                .in_unit(HeightUnit::Cm)
+                        ),
+                    )
+                    .await;
+                last_height_publish = Some(Instant::now());
+            }
+        }
+
+        if positions.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod mqtt_suite {
+    use crate::{
+        config_utils::{MqttConfig, Position},
+        height::{Height, HeightUnit, RawHeight},
+        loose_idasen::HARDWARE_LIMITS,
+        mqtt_bridge::{
+            discovery_messages, parse_command, removed_positions, DeskCommand, MqttTopics,
+        },
+    };
+
+    fn test_config() -> MqttConfig {
+        MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            base_topic: "trayasen".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    fn test_positions() -> Vec<Position> {
+        vec![Position {
+            name: "Standing Up".to_string(),
+            value: RawHeight(11000),
+            shortcut: None,
+        }]
+    }
+
+    #[test]
+    fn should_parse_commands() {
+        let topics = MqttTopics::new(&test_config(), "Desk 1234");
+
+        assert_eq!(
+            parse_command(&topics, "trayasen/desk_1234/height/set", b"110.5"),
+            Some(DeskCommand::MoveTo(Height::from_unit(
+                110.5,
+                HeightUnit::Cm
+            )))
+        );
+        assert_eq!(
+            parse_command(&topics, "trayasen/desk_1234/preset/set", b"Standing Up"),
+            Some(DeskCommand::MoveToPreset("Standing Up".to_string()))
+        );
+        assert_eq!(
+            parse_command(&topics, "trayasen/desk_1234/stop/set", b"PRESS"),
+            Some(DeskCommand::Stop)
+        );
+        assert_eq!(
+            parse_command(&topics, "trayasen/desk_1234/height/set", b"tall"),
+            None
+        );
+        assert_eq!(parse_command(&topics, "something/else", b"1"), None);
+    }
+
+    #[test]
+    fn should_announce_a_button_per_position() {
+        let topics = MqttTopics::new(&test_config(), "Desk 1234");
+        let messages =
+            discovery_messages(&topics, "Desk 1234", &test_positions(), 0, HARDWARE_LIMITS);
+
+        let (topic, payload) = messages
+            .iter()
+            .find(|(topic, _)| topic.contains("preset_"))
+            .unwrap();
+        assert_eq!(
+            topic,
+            "homeassistant/button/desk_1234/preset_standing_up/config"
+        );
+        assert_eq!(payload["command_topic"], "trayasen/desk_1234/preset/set");
+        assert_eq!(payload["payload_press"], "Standing Up");
+        assert_eq!(payload["device"]["name"], "Desk 1234");
+    }
+
+    #[test]
+    fn should_find_removed_and_renamed_positions() {
+        let published = test_positions();
+        let mut saved = test_positions();
+        saved[0].name = "Standing".to_string();
+        saved.push(Position {
+            name: "Sitting".to_string(),
+            value: RawHeight(7000),
+            shortcut: None,
+        });
+
+        assert_eq!(removed_positions(&published, &saved), published);
+        assert!(removed_positions(&saved, &saved).is_empty());
+    }
+
+    /// Needs a broker running on localhost:1883, eg. `mosquitto -v`. Run with `cargo test --features mqtt -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn should_publish_discovery_to_local_broker() {
+        use rumqttc::{Event, Packet, QoS};
+
+        use crate::mqtt_bridge::{create_client, publish_discovery, DiscoveryState};
+
+        let topics = MqttTopics::new(&test_config(), "Test Desk");
+        let (client, mut event_loop) = create_client(&test_config(), &topics);
+        client
+            .subscribe("homeassistant/+/test_desk/#", QoS::AtLeastOnce)
+            .await
+            .unwrap();
+        let discovery_state = DiscoveryState {
+            positions: test_positions(),
+            height_offset: 0,
+            height_limits: HARDWARE_LIMITS,
+        };
+        publish_discovery(&client, &topics, "Test Desk", &discovery_state, &[])
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 4 {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), event_loop.poll())
+                .await
+                .expect("Broker did not answer in time")
+                .unwrap();
+            if let Event::Incoming(Packet::Publish(publish)) = event {
+                received.push(publish.topic);
+            }
+        }
+        assert!(received.contains(&"homeassistant/number/test_desk/height/config".to_string()));
+    }
+}
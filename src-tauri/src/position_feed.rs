@@ -0,0 +1,68 @@
+/*
+  Live position of the connected desk, fed from the position notifications.
+  Everything interested in the height(MQTT, reminders, history, tray) watches this instead of talking to the desk.
+*/
+use futures::StreamExt;
+use tauri::{AppHandle, Manager};
+use tokio::sync::watch;
+
+use crate::{
+    desk_transport::DeskTransport,
+    loose_idasen::{self, ConnectedBtDevice, PositionSpeed, POSITION_UUID},
+};
+
+/// Latest known position of the desk - `None` while disconnected
+pub struct TauriPositionFeed(pub watch::Sender<Option<PositionSpeed>>);
+
+impl TauriPositionFeed {
+    pub fn new() -> Self {
+        TauriPositionFeed(watch::channel(None).0)
+    }
+}
+
+pub fn subscribe(app_handle: &AppHandle) -> watch::Receiver<Option<PositionSpeed>> {
+    app_handle.state::<TauriPositionFeed>().0.subscribe()
+}
+
+pub fn latest_position(app_handle: &AppHandle) -> Option<PositionSpeed> {
+    let position_feed = app_handle.state::<TauriPositionFeed>();
+    let latest_position = position_feed.0.borrow();
+    *latest_position
+}
+
+/// Publishes every position notification of the desk into the feed. Never resolves - drop it once the desk is gone.
+pub async fn forward_positions(
+    app_handle: &AppHandle,
+    desk: &ConnectedBtDevice<impl DeskTransport>,
+) {
+    let position_feed = app_handle.state::<TauriPositionFeed>();
+
+    match desk.device_instance.notifications().await {
+        Ok(mut notifications) => {
+            if let Ok(position_speed) = loose_idasen::get_position_and_speed(desk).await {
+                position_feed.0.send_replace(Some(position_speed));
+            }
+            while let Some(notification) = notifications.next().await {
+                if notification.uuid != POSITION_UUID {
+                    continue;
+                }
+                // One bad packet should not take the feed down with it
+                match loose_idasen::bytes_to_position_speed(&notification.value) {
+                    Some(position_speed) => {
+                        position_feed.0.send_replace(Some(position_speed));
+                    }
+                    None => log::warn!("Skipping garbled desk position: {:?}", notification.value),
+                }
+            }
+        }
+        Err(e) => log::error!("Error while listening to desk position: {}", e),
+    }
+
+    // Whoever dropped the connection will tell the supervisor about it
+    std::future::pending::<()>().await
+}
+
+/// Marks the position as unknown, eg. after the desk disconnects
+pub fn clear(app_handle: &AppHandle) {
+    app_handle.state::<TauriPositionFeed>().0.send_replace(None);
+}
@@ -0,0 +1,311 @@
+/*
+  Sharing saved positions between machines. Positions(with their shortcuts) are exported to a JSON or a TOML file:
+    [[saved_positions]]
+    name = "Standing"
+    value = 11000
+    shortcut = "CmdOrCtrl+Shift+1"
+  The format is picked by the extension of the file - `.toml` for TOML, anything else for JSON.
+*/
+use std::{fs, path::Path, str::FromStr};
+
+use serde_derive::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::{
+    app_error::AppError,
+    config_store,
+    config_utils::Position,
+    height::{HeightLimits, RawHeight},
+    loose_idasen::HARDWARE_LIMITS,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("Error while accessing the presets file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Presets file is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Presets file is not valid TOML: {0}")]
+    TomlRead(#[from] toml::de::Error),
+    #[error("Error while writing presets as TOML: {0}")]
+    TomlWrite(#[from] toml::ser::Error),
+    #[error("Position `{name}` has a height of {value}, which is not between {} and {}.", .limits.min, .limits.max)]
+    OutOfRange {
+        name: String,
+        value: RawHeight,
+        limits: HeightLimits,
+    },
+    #[error("Position `{0}` is in the presets file more than once.")]
+    DuplicateName(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresetFormat {
+    Json,
+    Toml,
+}
+
+impl PresetFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => PresetFormat::Toml,
+            _ => PresetFormat::Json,
+        }
+    }
+}
+
+/// What to do with the positions already saved
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Add the new positions, and overwrite the ones with the same name
+    Merge,
+    /// Throw away all the saved positions
+    Replace,
+    /// Add the new positions, and keep the saved ones with the same name as they are
+    SkipDuplicates,
+}
+
+impl FromStr for ImportMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            "skip-duplicates" | "skip_duplicates" => Ok(ImportMode::SkipDuplicates),
+            _ => Err(format!(
+                "Unknown import mode `{}` - use merge, replace or skip-duplicates.",
+                mode
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub removed: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct PresetFile {
+    saved_positions: Vec<Position>,
+}
+
+pub fn export_presets(positions: &[Position], format: PresetFormat) -> Result<String, PresetError> {
+    let preset_file = PresetFile {
+        saved_positions: positions.to_vec(),
+    };
+    Ok(match format {
+        PresetFormat::Json => serde_json::to_string_pretty(&preset_file)?,
+        PresetFormat::Toml => toml::to_string_pretty(&preset_file)?,
+    })
+}
+
+/// Reads the positions from a presets file and checks that the desk can actually reach all of them
+pub fn parse_presets(content: &str, format: PresetFormat) -> Result<Vec<Position>, PresetError> {
+    let preset_file = match format {
+        PresetFormat::Json => serde_json::from_str::<PresetFile>(content)?,
+        PresetFormat::Toml => toml::from_str::<PresetFile>(content)?,
+    };
+
+    check_limits(&preset_file.saved_positions, HARDWARE_LIMITS)?;
+    let mut positions: Vec<Position> = vec![];
+    for position in preset_file.saved_positions {
+        if positions.iter().any(|pos| pos.name == position.name) {
+            return Err(PresetError::DuplicateName(position.name));
+        }
+        positions.push(Position {
+            // Older configs used empty strings for missing shortcuts
+            shortcut: position.shortcut.filter(|shortcut| !shortcut.is_empty()),
+            ..position
+        });
+    }
+    Ok(positions)
+}
+
+/// Checks that all the positions are within the height limits of the desk they go to
+pub fn check_limits(positions: &[Position], limits: HeightLimits) -> Result<(), PresetError> {
+    match positions.iter().find(|pos| !limits.contains(pos.value)) {
+        Some(position) => Err(PresetError::OutOfRange {
+            name: position.name.clone(),
+            value: position.value,
+            limits,
+        }),
+        None => Ok(()),
+    }
+}
+
+pub fn export_presets_to_file(positions: &[Position], path: &Path) -> Result<(), PresetError> {
+    fs::write(
+        path,
+        export_presets(positions, PresetFormat::from_path(path))?,
+    )?;
+    Ok(())
+}
+
+pub fn read_presets_from_file(path: &Path) -> Result<Vec<Position>, PresetError> {
+    let content = fs::read_to_string(path)?;
+    parse_presets(&content, PresetFormat::from_path(path))
+}
+
+/// Puts the imported positions into the saved ones, according to the mode
+pub fn apply_import(
+    saved_positions: &mut Vec<Position>,
+    imported: Vec<Position>,
+    mode: ImportMode,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    if mode == ImportMode::Replace {
+        summary.removed = saved_positions.len();
+        saved_positions.clear();
+    }
+
+    for position in imported {
+        match saved_positions
+            .iter_mut()
+            .find(|pos| pos.name == position.name)
+        {
+            Some(_) if mode == ImportMode::SkipDuplicates => summary.skipped += 1,
+            Some(saved_position) => {
+                *saved_position = position;
+                summary.updated += 1;
+            }
+            None => {
+                saved_positions.push(position);
+                summary.added += 1;
+            }
+        }
+    }
+    summary
+}
+
+#[tauri::command]
+pub fn export_presets_to_path(app_handle: AppHandle, path: String) -> Result<(), AppError> {
+    let config = config_store::get_config(&app_handle);
+    export_presets_to_file(config.saved_positions(), Path::new(&path))?;
+    Ok(())
+}
+
+/// Imports positions from a file into the desk in use. Nothing changes if any of the positions is invalid,
+/// or outside the height limits of the desk.
+#[tauri::command]
+pub fn import_presets_from_path(
+    app_handle: AppHandle,
+    path: String,
+    mode: ImportMode,
+) -> Result<ImportSummary, AppError> {
+    let imported = read_presets_from_file(Path::new(&path))?;
+    check_limits(
+        &imported,
+        config_store::get_config(&app_handle).height_limits(),
+    )?;
+
+    let summary = config_store::update_config(&app_handle, |config| {
+        apply_import(config.saved_positions_mut(), imported, mode)
+    })?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod transfer_suite {
+    use crate::{
+        config_utils::Position,
+        height::{HeightLimits, RawHeight},
+        preset_transfer::{
+            apply_import, check_limits, export_presets, parse_presets, ImportMode, ImportSummary,
+            PresetError, PresetFormat,
+        },
+    };
+
+    fn position(name: &str, value: u16) -> Position {
+        Position {
+            name: name.to_string(),
+            value: RawHeight(value),
+            shortcut: None,
+        }
+    }
+
+    #[test]
+    fn should_round_trip_both_formats() {
+        let positions = vec![
+            Position {
+                shortcut: Some("CmdOrCtrl+Shift+1".to_string()),
+                ..position("Standing", 11000)
+            },
+            position("Sitting", 7500),
+        ];
+
+        for format in [PresetFormat::Json, PresetFormat::Toml] {
+            let exported = export_presets(&positions, format).unwrap();
+            assert_eq!(parse_presets(&exported, format).unwrap(), positions);
+        }
+    }
+
+    #[test]
+    fn should_reject_positions_out_of_range() {
+        let presets = r#"
+            [[saved_positions]]
+            name = "Too high"
+            value = 13000
+        "#;
+
+        assert!(matches!(
+            parse_presets(presets, PresetFormat::Toml),
+            Err(PresetError::OutOfRange {
+                value: RawHeight(13000),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_positions_outside_desk_limits() {
+        let positions = vec![position("Sitting", 7500), position("Standing", 11500)];
+        let limits = HeightLimits {
+            min: RawHeight(7000),
+            max: RawHeight(11000),
+        };
+
+        let result = check_limits(&positions, limits);
+
+        assert!(matches!(
+            result,
+            Err(PresetError::OutOfRange { name, limits: error_limits, .. })
+                if name == "Standing" && error_limits == limits
+        ));
+        assert!(check_limits(&positions[..1], limits).is_ok());
+    }
+
+    #[test]
+    fn should_import_in_every_mode() {
+        let saved = vec![position("Standing", 11000), position("Sitting", 7500)];
+        let imported = vec![position("Standing", 11500), position("Leaning", 9000)];
+
+        let mut merged = saved.clone();
+        let summary = apply_import(&mut merged, imported.clone(), ImportMode::Merge);
+        assert_eq!(merged[0], position("Standing", 11500));
+        assert_eq!(merged.len(), 3);
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 1,
+                updated: 1,
+                ..Default::default()
+            }
+        );
+
+        let mut skipped = saved.clone();
+        let summary = apply_import(&mut skipped, imported.clone(), ImportMode::SkipDuplicates);
+        assert_eq!(skipped[0], position("Standing", 11000));
+        assert_eq!(summary.skipped, 1);
+
+        let mut replaced = saved.clone();
+        let summary = apply_import(&mut replaced, imported.clone(), ImportMode::Replace);
+        assert_eq!(replaced, imported);
+        assert_eq!(summary.removed, 2);
+    }
+}
@@ -0,0 +1,393 @@
+/*
+  Sit/stand reminders. Tracks how long the desk has been in the sitting or standing posture(based on the live height),
+  and reminds the user to switch once they've been in one posture for too long.
+  Optionally moves the desk to the configured position along with the reminder.
+*/
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    config_store,
+    config_utils::{self, ReminderConfig},
+    height::RawHeight,
+    movement_utils, position_feed, tray_utils,
+};
+
+/// How often we check whether a reminder is due. Also picks up config changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Posture {
+    Sitting,
+    Standing,
+}
+
+impl Posture {
+    pub fn from_height(height: RawHeight, stand_threshold: RawHeight) -> Self {
+        if height >= stand_threshold {
+            Posture::Standing
+        } else {
+            Posture::Sitting
+        }
+    }
+
+    pub fn opposite(&self) -> Self {
+        match self {
+            Posture::Sitting => Posture::Standing,
+            Posture::Standing => Posture::Sitting,
+        }
+    }
+
+    /// How long the user may stay in this posture before being reminded
+    fn allowed_duration(&self, config: &ReminderConfig) -> Duration {
+        let minutes = match self {
+            Posture::Sitting => config.sit_minutes,
+            Posture::Standing => config.stand_minutes,
+        };
+        Duration::from_secs(minutes as u64 * 60)
+    }
+}
+
+/// Time spent in each posture. Time while the desk is disconnected is not counted.
+#[derive(Debug)]
+pub struct PostureTracker {
+    posture: Option<Posture>,
+    posture_since: Instant,
+    // Start of the posture, moved forward every time a reminder goes off
+    reminder_base: Instant,
+    last_observed: Instant,
+    sitting: Duration,
+    standing: Duration,
+}
+
+impl PostureTracker {
+    pub fn new(now: Instant) -> Self {
+        PostureTracker {
+            posture: None,
+            posture_since: now,
+            reminder_base: now,
+            last_observed: now,
+            sitting: Duration::ZERO,
+            standing: Duration::ZERO,
+        }
+    }
+
+    /// Record the posture the desk is currently in - `None` when unknown
+    pub fn observe(&mut self, posture: Option<Posture>, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_observed);
+        match self.posture {
+            Some(Posture::Sitting) => self.sitting += elapsed,
+            Some(Posture::Standing) => self.standing += elapsed,
+            None => {}
+        }
+
+        if posture != self.posture {
+            self.posture = posture;
+            self.posture_since = now;
+            self.reminder_base = now;
+        }
+        self.last_observed = now;
+    }
+
+    /// Posture the user should switch to, if they've been in the current one for too long.
+    /// Repeats every interval for as long as the user stays put.
+    pub fn due_reminder(&mut self, config: &ReminderConfig, now: Instant) -> Option<Posture> {
+        let posture = self.posture?;
+        if now.saturating_duration_since(self.reminder_base) < posture.allowed_duration(config) {
+            return None;
+        }
+        self.reminder_base = now;
+        Some(posture.opposite())
+    }
+
+    /// Start counting towards the next reminder from scratch, eg. after reminders get resumed
+    pub fn snooze(&mut self, now: Instant) {
+        self.reminder_base = now;
+    }
+
+    /// Current posture, time spent in it, and total time spent sitting and standing
+    pub fn status(&self, now: Instant) -> (Option<Posture>, Duration, Duration, Duration) {
+        let unrecorded = now.saturating_duration_since(self.last_observed);
+        let (sitting, standing) = match self.posture {
+            Some(Posture::Sitting) => (self.sitting + unrecorded, self.standing),
+            Some(Posture::Standing) => (self.sitting, self.standing + unrecorded),
+            None => (self.sitting, self.standing),
+        };
+        (
+            self.posture,
+            now.saturating_duration_since(self.posture_since),
+            sitting,
+            standing,
+        )
+    }
+}
+
+pub struct TauriReminderState {
+    paused: AtomicBool,
+    tracker: Mutex<PostureTracker>,
+}
+
+impl TauriReminderState {
+    pub fn new() -> Self {
+        TauriReminderState {
+            paused: AtomicBool::new(false),
+            tracker: Mutex::new(PostureTracker::new(Instant::now())),
+        }
+    }
+
+    fn with_tracker<R>(&self, f: impl FnOnce(&mut PostureTracker) -> R) -> R {
+        let mut tracker = self
+            .tracker
+            .lock()
+            .expect("Error while unwrapping posture tracker");
+        f(&mut tracker)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PostureStatus {
+    pub paused: bool,
+    pub posture: Option<Posture>,
+    pub minutes_in_posture: u64,
+    pub sitting_minutes: u64,
+    pub standing_minutes: u64,
+}
+
+#[tauri::command]
+pub fn get_posture_status(app_handle: AppHandle) -> PostureStatus {
+    let reminder_state = app_handle.state::<TauriReminderState>();
+    let (posture, in_posture, sitting, standing) =
+        reminder_state.with_tracker(|tracker| tracker.status(Instant::now()));
+    PostureStatus {
+        paused: reminder_state.paused.load(Ordering::SeqCst),
+        posture,
+        minutes_in_posture: in_posture.as_secs() / 60,
+        sitting_minutes: sitting.as_secs() / 60,
+        standing_minutes: standing.as_secs() / 60,
+    }
+}
+
+/// Pauses or resumes the reminders, used by the tray item
+pub fn toggle_paused(app_handle: &AppHandle) {
+    let reminder_state = app_handle.state::<TauriReminderState>();
+    let was_paused = reminder_state.paused.fetch_xor(true, Ordering::SeqCst);
+    if was_paused {
+        // Otherwise a reminder held back during the pause would go off right away
+        reminder_state.with_tracker(|tracker| tracker.snooze(Instant::now()));
+    }
+    log::info!("Reminders paused: {}", !was_paused);
+    update_tray_item(app_handle);
+}
+
+/// Puts the pause state into the tray item. Has to be called every time the tray menu is recreated.
+pub fn update_tray_item(app_handle: &AppHandle) {
+    let paused = app_handle
+        .state::<TauriReminderState>()
+        .paused
+        .load(Ordering::SeqCst);
+    let title = if paused {
+        "Resume reminders"
+    } else {
+        "Pause reminders"
+    };
+    // Item is only in the menu when reminders are configured
+    if let Some(item) = app_handle
+        .tray_handle()
+        .try_get_item(config_utils::PAUSE_REMINDERS_ID)
+    {
+        _ = item.set_title(title);
+    }
+}
+
+pub fn spawn_reminder_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut positions = position_feed::subscribe(&app_handle);
+        let mut check = tokio::time::interval(CHECK_INTERVAL);
+        let mut reminder_config = config_store::get_config(&app_handle).reminders;
+
+        loop {
+            let is_check = tokio::select! {
+                changed = positions.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    false
+                }
+                _ = check.tick() => true,
+            };
+            if is_check {
+                reminder_config = config_store::get_config(&app_handle).reminders;
+            }
+
+            let posture = match (&reminder_config, *positions.borrow_and_update()) {
+                (Some(reminder_config), Some(position_speed)) => Some(Posture::from_height(
+                    position_speed.position,
+                    reminder_config.stand_threshold,
+                )),
+                _ => None,
+            };
+            let reminder_state = app_handle.state::<TauriReminderState>();
+            let now = Instant::now();
+            reminder_state.with_tracker(|tracker| tracker.observe(posture, now));
+
+            let Some(reminder_config) = &reminder_config else {
+                continue;
+            };
+            if !is_check || reminder_state.paused.load(Ordering::SeqCst) {
+                continue;
+            }
+            if let Some(switch_to) =
+                reminder_state.with_tracker(|tracker| tracker.due_reminder(reminder_config, now))
+            {
+                remind(&app_handle, reminder_config, switch_to);
+            }
+        }
+    });
+}
+
+fn remind(app_handle: &AppHandle, reminder_config: &ReminderConfig, switch_to: Posture) {
+    let (message, preset) = match switch_to {
+        Posture::Standing => (
+            format!(
+                "You've been sitting for {} minutes - time to stand up!",
+                reminder_config.sit_minutes
+            ),
+            &reminder_config.stand_preset,
+        ),
+        Posture::Sitting => (
+            format!(
+                "You've been standing for {} minutes - time to sit down!",
+                reminder_config.stand_minutes
+            ),
+            &reminder_config.sit_preset,
+        ),
+    };
+    tray_utils::show_notification(app_handle, &message);
+
+    if let Some(preset) = preset {
+        let config = config_store::get_config(app_handle);
+        match config
+            .saved_positions()
+            .iter()
+            .find(|pos| &pos.name == preset)
+        {
+            Some(position) => movement_utils::spawn_move_to_target(app_handle, position.value),
+            None => log::warn!("Reminder position not found: {}", preset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod reminding_suite {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        config_utils::ReminderConfig,
+        height::RawHeight,
+        reminder_scheduler::{Posture, PostureTracker},
+    };
+
+    fn test_config() -> ReminderConfig {
+        ReminderConfig {
+            sit_minutes: 45,
+            stand_minutes: 15,
+            stand_threshold: RawHeight(9500),
+            sit_preset: None,
+            stand_preset: None,
+        }
+    }
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn should_tell_posture_by_height() {
+        let stand_threshold = RawHeight(9500);
+
+        assert_eq!(
+            Posture::from_height(RawHeight(7500), stand_threshold),
+            Posture::Sitting
+        );
+        assert_eq!(
+            Posture::from_height(RawHeight(9500), stand_threshold),
+            Posture::Standing
+        );
+        assert_eq!(
+            Posture::from_height(RawHeight(11000), stand_threshold),
+            Posture::Standing
+        );
+    }
+
+    #[test]
+    fn should_remind_once_per_interval() {
+        let start = Instant::now();
+        let mut tracker = PostureTracker::new(start);
+        tracker.observe(Some(Posture::Sitting), start);
+
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(44)),
+            None
+        );
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(45)),
+            Some(Posture::Standing)
+        );
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(60)),
+            None
+        );
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(90)),
+            Some(Posture::Standing)
+        );
+    }
+
+    #[test]
+    fn should_restart_counting_after_posture_change() {
+        let start = Instant::now();
+        let mut tracker = PostureTracker::new(start);
+        tracker.observe(Some(Posture::Sitting), start);
+        tracker.observe(Some(Posture::Standing), start + minutes(40));
+
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(50)),
+            None
+        );
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(55)),
+            Some(Posture::Sitting)
+        );
+
+        let (posture, in_posture, sitting, _) = tracker.status(start + minutes(55));
+        assert_eq!(posture, Some(Posture::Standing));
+        assert_eq!(in_posture, minutes(15));
+        assert_eq!(sitting, minutes(40));
+    }
+
+    #[test]
+    fn should_not_count_time_while_disconnected() {
+        let start = Instant::now();
+        let mut tracker = PostureTracker::new(start);
+        tracker.observe(Some(Posture::Sitting), start);
+        tracker.observe(None, start + minutes(30));
+        tracker.observe(Some(Posture::Sitting), start + minutes(90));
+
+        assert_eq!(
+            tracker.due_reminder(&test_config(), start + minutes(100)),
+            None
+        );
+        let (_, _, sitting, standing) = tracker.status(start + minutes(100));
+        assert_eq!(sitting, minutes(40));
+        assert_eq!(standing, Duration::ZERO);
+    }
+}
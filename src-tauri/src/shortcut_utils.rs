@@ -0,0 +1,85 @@
+// Set of utils to register and unregister the global shortcuts saved in the config
+
+use tauri::{AppHandle, GlobalShortcutManager};
+
+use crate::{config_utils::ConfigData, desk_groups, movement_utils};
+
+/// Registers the shortcuts of all the positions(including the ones of desk groups), and the one stopping the desk
+pub fn register_shortcuts(app_handle: &AppHandle, config: &ConfigData) {
+    let mut shortcut_manager = app_handle.global_shortcut_manager();
+
+    // A lot of combinations do not not seem to be supported by tauri. Don't know for sure since there are no docs.
+    for pos in config.saved_positions().iter() {
+        if let Some(shortcut_key) = &pos.shortcut {
+            if !shortcut_key.is_empty() {
+                // Each shortcut needs it's own clone; we do not want to consume the app state
+                let cloned_app_handle = app_handle.clone();
+                let value = pos.value;
+                _ = shortcut_manager.register(shortcut_key.as_str(), move || {
+                    movement_utils::spawn_move_to_target(&cloned_app_handle, value);
+                });
+            }
+        }
+    }
+
+    for group in config.groups.iter() {
+        for pos in group.saved_positions.iter() {
+            if let Some(shortcut_key) = &pos.shortcut {
+                if !shortcut_key.is_empty() {
+                    let cloned_app_handle = app_handle.clone();
+                    let group = group.clone();
+                    let value = pos.value;
+                    _ = shortcut_manager.register(shortcut_key.as_str(), move || {
+                        desk_groups::spawn_move_group_to_target(
+                            &cloned_app_handle,
+                            group.clone(),
+                            value,
+                        );
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(stop_shortcut) = &config.stop_shortcut {
+        if !stop_shortcut.is_empty() {
+            let cloned_app_handle = app_handle.clone();
+            _ = shortcut_manager.register(stop_shortcut.as_str(), move || {
+                movement_utils::spawn_stop(&cloned_app_handle);
+            });
+        }
+    }
+}
+
+/// Unregisters every shortcut registered by `register_shortcuts` for the same config
+pub fn unregister_shortcuts(app_handle: &AppHandle, config: &ConfigData) {
+    let mut shortcut_manager = app_handle.global_shortcut_manager();
+    let group_positions = config
+        .groups
+        .iter()
+        .flat_map(|group| group.saved_positions.iter());
+    let shortcuts = config
+        .saved_positions()
+        .iter()
+        .chain(group_positions)
+        .filter_map(|pos| pos.shortcut.as_ref())
+        .chain(config.stop_shortcut.as_ref());
+
+    for shortcut in shortcuts {
+        if !shortcut.is_empty() {
+            _ = shortcut_manager.unregister(shortcut.as_str());
+        }
+    }
+}
+
+/// Swaps the registered shortcuts for the ones of the new config, if anything they depend on has changed
+pub fn update_shortcuts(app_handle: &AppHandle, old_config: &ConfigData, new_config: &ConfigData) {
+    if old_config.saved_positions() == new_config.saved_positions()
+        && old_config.groups == new_config.groups
+        && old_config.stop_shortcut == new_config.stop_shortcut
+    {
+        return;
+    }
+    unregister_shortcuts(app_handle, old_config);
+    register_shortcuts(app_handle, new_config);
+}
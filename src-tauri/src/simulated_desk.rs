@@ -0,0 +1,378 @@
+/*
+  In-memory desk speaking the same protocol as an Idasen desk, used to test `loose_idasen` without bluetooth.
+  It models the height, the speed, position notifications, obstructions and dropped connections.
+*/
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use btleplug::api::{BDAddr, CharPropFlags, Characteristic, ValueNotification, WriteType};
+use tokio::{sync::broadcast, time::Instant};
+use uuid::Uuid;
+
+use crate::{
+    desk_transport::{DeskTransport, NotificationStream},
+    loose_idasen::{CONTROL_UUID, DOWN, MAX_HEIGHT, MIN_HEIGHT, POSITION_UUID, STOP, UP},
+};
+
+const SERVICE_UUID: Uuid = Uuid::from_bytes([
+    0x99, 0xfa, 0x00, 0x01, 0x33, 0x8a, 0x10, 0x24, 0x8a, 0x49, 0x00, 0x9c, 0x02, 0x15, 0xf7, 0x8a,
+]);
+
+/// How often the simulated motor updates the height
+const TICK: Duration = Duration::from_millis(50);
+/// For how long a single UP/DOWN command keeps the desk moving
+const COMMAND_LEASE: Duration = Duration::from_millis(500);
+/// For how long the desk keeps moving after a STOP
+const BRAKING_TIME: Duration = Duration::from_millis(100);
+/// Speed of the desk in tenth mm per second
+pub const DEFAULT_SPEED: i16 = 380;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Idle,
+    Moving { up: bool, until: Instant },
+    Braking { up: bool, until: Instant },
+}
+
+#[derive(Debug)]
+struct SimulatedState {
+    // tenth mm
+    height: u16,
+    // tenth mm per second, negative when going down
+    speed: i16,
+    motion: Motion,
+    // Height at which something blocks the desk
+    obstruction: Option<u16>,
+    // For how long the motor stays still after a move command, before the desk starts going
+    start_delay: Duration,
+    // Until when the desk is still starting up
+    starting_until: Option<Instant>,
+    reachable: bool,
+    // Whether the control characteristic is there - other devices might only look like desks
+    controllable: bool,
+    connected: bool,
+    // Bumped on every connection, so that a motor from a dropped connection does not keep running
+    generation: u64,
+    services_discovered: bool,
+    subscribed: bool,
+    notifier: Option<broadcast::Sender<ValueNotification>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatedDesk {
+    state: Arc<Mutex<SimulatedState>>,
+}
+
+impl SimulatedDesk {
+    pub fn new(height: u16) -> Self {
+        SimulatedDesk {
+            state: Arc::new(Mutex::new(SimulatedState {
+                height,
+                speed: 0,
+                motion: Motion::Idle,
+                obstruction: None,
+                start_delay: Duration::ZERO,
+                starting_until: None,
+                reachable: true,
+                controllable: true,
+                connected: false,
+                generation: 0,
+                services_discovered: false,
+                subscribed: false,
+                notifier: None,
+            })),
+        }
+    }
+
+    /// A desk that refuses every connection attempt, eg. because it's out of range
+    pub fn unreachable(height: u16) -> Self {
+        let desk = SimulatedDesk::new(height);
+        desk.state.lock().unwrap().reachable = false;
+        desk
+    }
+
+    /// A device which can be connected to, but has no control characteristic
+    pub fn uncontrollable(height: u16) -> Self {
+        let desk = SimulatedDesk::new(height);
+        desk.state.lock().unwrap().controllable = false;
+        desk
+    }
+
+    pub fn height(&self) -> u16 {
+        self.state.lock().unwrap().height
+    }
+
+    pub fn speed(&self) -> i16 {
+        self.state.lock().unwrap().speed
+    }
+
+    pub fn is_subscribed(&self) -> bool {
+        self.state.lock().unwrap().subscribed
+    }
+
+    /// Put something in the way of the desk, so that it cannot move past the provided height
+    pub fn obstruct_at(&self, height: u16) {
+        self.state.lock().unwrap().obstruction = Some(height);
+    }
+
+    /// Make the desk wait for the provided time before it starts moving, like a slow motor
+    pub fn delay_start(&self, delay: Duration) {
+        self.state.lock().unwrap().start_delay = delay;
+    }
+
+    /// Simulate the desk going out of range - every pending and future operation fails until reconnected
+    pub fn drop_connection(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.connected = false;
+        state.subscribed = false;
+        state.speed = 0;
+        state.motion = Motion::Idle;
+        // Dropping the sender ends every notification stream handed out so far
+        state.notifier = None;
+    }
+
+    fn characteristic(uuid: Uuid, properties: CharPropFlags) -> Characteristic {
+        Characteristic {
+            uuid,
+            service_uuid: SERVICE_UUID,
+            properties,
+            descriptors: BTreeSet::new(),
+        }
+    }
+
+    fn encode(height: u16, speed: i16) -> Vec<u8> {
+        let mut bytes = (height - MIN_HEIGHT.0).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&speed.to_le_bytes());
+        bytes
+    }
+
+    fn check_connected(state: &SimulatedState) -> btleplug::Result<()> {
+        if state.connected {
+            Ok(())
+        } else {
+            Err(btleplug::Error::NotConnected)
+        }
+    }
+
+    /// Advance the simulation by one tick. Returns false once the connection is gone.
+    fn tick(&self, generation: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.connected || state.generation != generation {
+            return false;
+        }
+
+        let now = Instant::now();
+        let going_up = match state.motion {
+            Motion::Moving { up, until } | Motion::Braking { up, until } if now < until => Some(up),
+            _ => None,
+        };
+        let old = (state.height, state.speed);
+        let starting = state.starting_until.is_some_and(|until| now < until);
+
+        match going_up {
+            Some(_) if starting => {}
+            Some(up) => {
+                let step = (DEFAULT_SPEED as u128 * TICK.as_millis() / 1000) as u16;
+                let mut height = if up {
+                    state.height.saturating_add(step).min(MAX_HEIGHT.0)
+                } else {
+                    state.height.saturating_sub(step).max(MIN_HEIGHT.0)
+                };
+                match state.obstruction {
+                    Some(obstruction) if up && state.height <= obstruction => {
+                        height = height.min(obstruction)
+                    }
+                    Some(obstruction) if !up && state.height >= obstruction => {
+                        height = height.max(obstruction)
+                    }
+                    _ => {}
+                }
+                state.speed = if height == state.height {
+                    0
+                } else if up {
+                    DEFAULT_SPEED
+                } else {
+                    -DEFAULT_SPEED
+                };
+                state.height = height;
+            }
+            None => {
+                state.motion = Motion::Idle;
+                state.speed = 0;
+            }
+        }
+
+        if old != (state.height, state.speed) && state.subscribed {
+            if let Some(notifier) = &state.notifier {
+                _ = notifier.send(ValueNotification {
+                    uuid: POSITION_UUID,
+                    value: SimulatedDesk::encode(state.height, state.speed),
+                });
+            }
+        }
+        true
+    }
+}
+
+#[async_trait]
+impl DeskTransport for SimulatedDesk {
+    fn peripheral_id(&self) -> String {
+        "simulated-desk".to_string()
+    }
+
+    fn address(&self) -> BDAddr {
+        BDAddr::default()
+    }
+
+    async fn connect(&self) -> btleplug::Result<()> {
+        let generation = {
+            let mut state = self.state.lock().unwrap();
+            if !state.reachable {
+                return Err(btleplug::Error::DeviceNotFound);
+            }
+            if state.connected {
+                return Ok(());
+            }
+            state.connected = true;
+            state.generation += 1;
+            state.notifier = Some(broadcast::channel(64).0);
+            state.generation
+        };
+
+        // The "motor" - runs for as long as the connection is alive
+        let desk = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TICK);
+            loop {
+                interval.tick().await;
+                if !desk.tick(generation) {
+                    break;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn is_connected(&self) -> btleplug::Result<bool> {
+        Ok(self.state.lock().unwrap().connected)
+    }
+
+    async fn discover_services(&self) -> btleplug::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        SimulatedDesk::check_connected(&state)?;
+        state.services_discovered = true;
+        Ok(())
+    }
+
+    fn characteristics(&self) -> BTreeSet<Characteristic> {
+        let state = self.state.lock().unwrap();
+        if !state.services_discovered {
+            return BTreeSet::new();
+        }
+        let mut characteristics = BTreeSet::from([SimulatedDesk::characteristic(
+            POSITION_UUID,
+            CharPropFlags::READ | CharPropFlags::NOTIFY,
+        )]);
+        if state.controllable {
+            characteristics.insert(SimulatedDesk::characteristic(
+                CONTROL_UUID,
+                CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE,
+            ));
+        }
+        characteristics
+    }
+
+    async fn read(&self, characteristic: &Characteristic) -> btleplug::Result<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        SimulatedDesk::check_connected(&state)?;
+        if characteristic.uuid != POSITION_UUID {
+            return Err(btleplug::Error::NotSupported(
+                "Reading a non-position characteristic".to_string(),
+            ));
+        }
+        Ok(SimulatedDesk::encode(state.height, state.speed))
+    }
+
+    async fn write(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        _write_type: WriteType,
+    ) -> btleplug::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        SimulatedDesk::check_connected(&state)?;
+        if characteristic.uuid != CONTROL_UUID {
+            return Err(btleplug::Error::NotSupported(
+                "Writing to a non-control characteristic".to_string(),
+            ));
+        }
+
+        let now = Instant::now();
+        if (data == UP || data == DOWN) && state.motion == Motion::Idle {
+            state.starting_until = Some(now + state.start_delay);
+        }
+        state.motion = if data == UP {
+            Motion::Moving {
+                up: true,
+                until: now + COMMAND_LEASE,
+            }
+        } else if data == DOWN {
+            Motion::Moving {
+                up: false,
+                until: now + COMMAND_LEASE,
+            }
+        } else if data == STOP {
+            match state.motion {
+                Motion::Moving { up, .. } => Motion::Braking {
+                    up,
+                    until: now + BRAKING_TIME,
+                },
+                other => other,
+            }
+        } else {
+            return Err(btleplug::Error::Other(
+                format!("Unknown command: {:?}", data).into(),
+            ));
+        };
+        Ok(())
+    }
+
+    async fn subscribe(&self, characteristic: &Characteristic) -> btleplug::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        SimulatedDesk::check_connected(&state)?;
+        if characteristic.uuid != POSITION_UUID {
+            return Err(btleplug::Error::NotSupported(
+                "Subscribing to a non-position characteristic".to_string(),
+            ));
+        }
+        state.subscribed = true;
+        Ok(())
+    }
+
+    async fn notifications(&self) -> btleplug::Result<NotificationStream> {
+        let state = self.state.lock().unwrap();
+        SimulatedDesk::check_connected(&state)?;
+        let receiver = state
+            .notifier
+            .as_ref()
+            .ok_or(btleplug::Error::NotConnected)?
+            .subscribe();
+
+        Ok(Box::pin(futures::stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(notification) => return Some((notification, receiver)),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )))
+    }
+}
@@ -0,0 +1,63 @@
+/*
+  Live height of the desk in the tray header and tooltip.
+  Position notifications come in several times a second while the desk moves, so the tray gets updated at most every `UPDATE_INTERVAL`.
+*/
+use std::time::Duration;
+
+use tauri::AppHandle;
+
+use crate::{
+    config_utils::ConfigData, desk_supervisor, loose_idasen::PositionSpeed, position_feed,
+};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Height as shown in the tray, eg. `72.5 cm` or `28.5 in, moving`
+pub fn describe_height(position_speed: &PositionSpeed, config: &ConfigData) -> String {
+    let height = config.format_height(position_speed.position);
+    if position_speed.speed != 0 {
+        format!("{}, moving", height)
+    } else {
+        height
+    }
+}
+
+pub fn spawn_tray_height(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut positions = position_feed::subscribe(&app_handle);
+        while positions.changed().await.is_ok() {
+            desk_supervisor::update_tray_header(&app_handle);
+            // Positions sent in the meantime get picked up in one go afterwards
+            tokio::time::sleep(UPDATE_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tray_height_suite {
+    use crate::{
+        config_utils::ConfigData,
+        height::{HeightUnit, RawHeight},
+        loose_idasen::PositionSpeed,
+        tray_height::describe_height,
+    };
+
+    #[test]
+    fn should_describe_height_in_unit() {
+        let mut config = ConfigData::default();
+        config.select_desk("Desk 1234", None, None);
+        config.active_desk_mut().height_offset = 250;
+        let standing_still = PositionSpeed {
+            position: RawHeight(7000),
+            speed: 0,
+        };
+        let going_down = PositionSpeed {
+            position: RawHeight(6100),
+            speed: -120,
+        };
+
+        assert_eq!(describe_height(&standing_still, &config), "72.5 cm");
+        config.height_unit = HeightUnit::Inches;
+        assert_eq!(describe_height(&going_down, &config), "25.0 in, moving");
+    }
+}
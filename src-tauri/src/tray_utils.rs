@@ -1,6 +1,9 @@
-use tauri::AppHandle;
+use tauri::{api::notification::Notification, AppHandle};
 
-use crate::WindowInitUtils;
+use crate::{
+    config_utils::{self, ConfigData},
+    desk_supervisor, reminder_scheduler, WindowInitUtils,
+};
 
 pub fn handle_exit_menu_click() {
     std::process::exit(0);
@@ -44,3 +47,25 @@ pub fn handle_manage_positions_menu_click(app: &AppHandle) {
             ),
         );
 }
+
+/// Shows a native notification - used to tell the user about things happening in the background
+pub fn show_notification(app: &AppHandle, body: &str) {
+    let notification = Notification::new(&app.config().tauri.bundle.identifier)
+        .title("Trayasen")
+        .body(body)
+        .show();
+    if let Err(e) = notification {
+        log::error!("Error while showing a notification: {}", e);
+    }
+}
+
+/// Since all the positions are tray elements, the whole tray menu has to be recreated whenever the config changes
+pub fn refresh_tray_menu(app: &AppHandle, config: &ConfigData) {
+    let main_menu = config_utils::create_main_tray_menu(config);
+    if let Err(e) = app.tray_handle().set_menu(main_menu) {
+        log::error!("Error while refreshing tray menu: {}", e);
+        return;
+    }
+    desk_supervisor::update_tray_header(app);
+    reminder_scheduler::update_tray_item(app);
+}
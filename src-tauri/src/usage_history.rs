@@ -0,0 +1,433 @@
+/*
+  History of desk heights, saved to `idasen-tray-history.jsonl` next to the config file.
+  Every line is a height the desk settled at(or `null` once it disconnects):
+    {"at": 1700000000, "height": 7500}
+  While connected, the current height is also written every few minutes, so that time when the app was not running
+  can be told apart from time spent at a single height.
+*/
+use std::{
+    fs::{read_to_string, OpenOptions},
+    io::Write,
+    time::Duration,
+};
+
+use chrono::{Days, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string};
+use tauri::AppHandle;
+
+use crate::{
+    config_store,
+    config_utils::{self, DEFAULT_STAND_THRESHOLD},
+    height::RawHeight,
+    position_feed,
+};
+
+static HISTORY_FILE_NAME: &str = "idasen-tray-history.jsonl";
+
+/// How often the height gets written while the desk stays put
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Longest time a single entry can account for - anything longer means the app was not running
+const MAX_ENTRY_SECONDS: i64 = 2 * 5 * 60;
+/// How many days the statistics cover, including today
+const STATS_DAYS: u64 = 7;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct HistoryEntry {
+    /// Unix timestamp in seconds
+    pub at: i64,
+    /// Height in tenth mm - `None` once the desk disconnects
+    pub height: Option<RawHeight>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct PeriodStats {
+    pub sitting_minutes: u64,
+    pub standing_minutes: u64,
+    /// How many times the user switched between sitting and standing
+    pub transitions: u32,
+    pub longest_sitting_minutes: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DayStats {
+    /// Local date, eg. `2023-12-24`
+    pub date: String,
+    #[serde(flatten)]
+    pub stats: PeriodStats,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UsageStats {
+    /// Last days, oldest first
+    pub days: Vec<DayStats>,
+    /// All of the days summed up
+    pub week: PeriodStats,
+}
+
+// Same as `PeriodStats`, but in seconds, so that rounding happens only once
+#[derive(Debug, Default, Clone)]
+struct Totals {
+    sitting: i64,
+    standing: i64,
+    transitions: u32,
+    longest_sitting: i64,
+}
+
+impl Totals {
+    fn to_stats(&self) -> PeriodStats {
+        PeriodStats {
+            sitting_minutes: (self.sitting / 60) as u64,
+            standing_minutes: (self.standing / 60) as u64,
+            transitions: self.transitions,
+            longest_sitting_minutes: (self.longest_sitting / 60) as u64,
+        }
+    }
+}
+
+/// Span of time the desk spent at a single posture
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: i64,
+    end: i64,
+    standing: bool,
+}
+
+fn get_history_path() -> String {
+    config_utils::get_data_file_path(HISTORY_FILE_NAME)
+}
+
+fn now_timestamp() -> i64 {
+    Utc::now().timestamp()
+}
+
+pub fn append_entry(entry: &HistoryEntry) -> std::io::Result<()> {
+    let mut history_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_history_path())?;
+    writeln!(history_file, "{}", to_string(entry)?)
+}
+
+/// All the entries saved so far. Lines which cannot be parsed(eg. cut off by a crash) are skipped.
+pub fn read_entries() -> Vec<HistoryEntry> {
+    let Ok(history) = read_to_string(get_history_path()) else {
+        return vec![];
+    };
+    history
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match from_str::<HistoryEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                log::warn!("Skipping broken history entry: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Writes every height the desk settles at into the history
+pub fn spawn_history_recorder(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut positions = position_feed::subscribe(&app_handle);
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut last_recorded: Option<RawHeight> = None;
+
+        loop {
+            let is_heartbeat = tokio::select! {
+                changed = positions.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    false
+                }
+                _ = heartbeat.tick() => true,
+            };
+
+            let height = match *positions.borrow_and_update() {
+                // Only the height the desk ends up at matters
+                Some(position_speed) if position_speed.speed != 0 => continue,
+                Some(position_speed) => Some(position_speed.position),
+                None => None,
+            };
+            let is_still_connected = is_heartbeat && height.is_some();
+            if height == last_recorded && !is_still_connected {
+                continue;
+            }
+
+            let entry = HistoryEntry {
+                at: now_timestamp(),
+                height,
+            };
+            match append_entry(&entry) {
+                Ok(()) => last_recorded = height,
+                Err(e) => log::error!("Error while saving desk history: {}", e),
+            }
+        }
+    });
+}
+
+/// Daily and weekly sit/stand totals for the frontend to chart
+#[tauri::command]
+pub fn get_usage_stats(app_handle: AppHandle) -> UsageStats {
+    let stand_threshold = config_store::get_config(&app_handle)
+        .reminders
+        .map(|reminders| reminders.stand_threshold)
+        .unwrap_or(DEFAULT_STAND_THRESHOLD);
+    compute_stats(&read_entries(), now_timestamp(), stand_threshold, &Local)
+}
+
+fn to_spans(entries: &[HistoryEntry], now: i64, stand_threshold: RawHeight) -> Vec<Span> {
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            let height = entry.height?;
+            let next_at = entries.get(i + 1).map(|next| next.at).unwrap_or(now);
+            let end = next_at.min(entry.at.saturating_add(MAX_ENTRY_SECONDS));
+            (end > entry.at).then_some(Span {
+                start: entry.at,
+                end,
+                standing: height >= stand_threshold,
+            })
+        })
+        .collect()
+}
+
+fn date_of<Tz: TimeZone>(timestamp: i64, tz: &Tz) -> NaiveDate {
+    tz.timestamp_opt(timestamp, 0)
+        .earliest()
+        .map(|date_time| date_time.date_naive())
+        .unwrap_or_default()
+}
+
+fn start_of_day<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .map(|date_time| date_time.timestamp())
+        // Midnight might not exist when the clock moves forward
+        .unwrap_or_else(|| midnight.and_utc().timestamp())
+}
+
+pub fn compute_stats<Tz: TimeZone>(
+    entries: &[HistoryEntry],
+    now: i64,
+    stand_threshold: RawHeight,
+    tz: &Tz,
+) -> UsageStats {
+    let today = date_of(now, tz);
+    let first_day = today - Days::new(STATS_DAYS - 1);
+    let period_start = start_of_day(first_day, tz);
+    let mut days = vec![Totals::default(); STATS_DAYS as usize];
+    // `None` for timestamps outside of the period, eg. entries written before the clock was moved back
+    let day_index = |timestamp: i64| {
+        usize::try_from((date_of(timestamp, tz) - first_day).num_days())
+            .ok()
+            .filter(|index| *index < STATS_DAYS as usize)
+    };
+
+    let spans = to_spans(entries, now, stand_threshold);
+    for span in &spans {
+        // Spans crossing midnight count towards both days
+        let mut start = span.start.max(period_start);
+        let span_end = span.end.min(now);
+        while start < span_end {
+            let next_midnight = start_of_day(date_of(start, tz) + Days::new(1), tz);
+            if next_midnight <= start {
+                break;
+            }
+            let end = span_end.min(next_midnight);
+            if let Some(day) = day_index(start).and_then(|index| days.get_mut(index)) {
+                if span.standing {
+                    day.standing += end - start;
+                } else {
+                    day.sitting += end - start;
+                }
+            }
+            start = end;
+        }
+    }
+
+    let mut streak_start: Option<i64> = None;
+    for (i, span) in spans.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|i| spans[i]);
+        let is_contiguous = previous.is_some_and(|previous| previous.end == span.start);
+
+        if is_contiguous
+            && previous.is_some_and(|previous| previous.standing != span.standing)
+            && (period_start..=now).contains(&span.start)
+        {
+            if let Some(day) = day_index(span.start).and_then(|index| days.get_mut(index)) {
+                day.transitions += 1;
+            }
+        }
+
+        if span.standing {
+            streak_start = None;
+            continue;
+        }
+        if !is_contiguous || streak_start.is_none() {
+            streak_start = Some(span.start);
+        }
+        // Streak is counted towards the day it started at
+        let start = streak_start.unwrap_or(span.start).max(period_start);
+        let end = span.end.min(now);
+        if start < end {
+            if let Some(day) = day_index(start).and_then(|index| days.get_mut(index)) {
+                day.longest_sitting = day.longest_sitting.max(end - start);
+            }
+        }
+    }
+
+    let week = days.iter().fold(Totals::default(), |week, day| Totals {
+        sitting: week.sitting + day.sitting,
+        standing: week.standing + day.standing,
+        transitions: week.transitions + day.transitions,
+        longest_sitting: week.longest_sitting.max(day.longest_sitting),
+    });
+
+    UsageStats {
+        days: days
+            .iter()
+            .enumerate()
+            .map(|(i, day)| DayStats {
+                date: (first_day + Days::new(i as u64)).to_string(),
+                stats: day.to_stats(),
+            })
+            .collect(),
+        week: week.to_stats(),
+    }
+}
+
+#[cfg(test)]
+mod stats_suite {
+    use chrono::{TimeZone, Utc};
+
+    use crate::{
+        height::RawHeight,
+        usage_history::{compute_stats, HistoryEntry, PeriodStats},
+    };
+
+    const THRESHOLD: RawHeight = RawHeight(9500);
+    const SITTING: Option<RawHeight> = Some(RawHeight(7500));
+    const STANDING: Option<RawHeight> = Some(RawHeight(11000));
+
+    /// Timestamp of the provided time on 2023-12-24 UTC
+    fn at(hour: u32, minute: u32) -> i64 {
+        Utc.with_ymd_and_hms(2023, 12, 24, hour, minute, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    /// Entries every 5 minutes from `from` to `to`, as the recorder writes them
+    fn still(from: i64, to: i64, height: Option<RawHeight>) -> Vec<HistoryEntry> {
+        (from..to)
+            .step_by(5 * 60)
+            .map(|at| HistoryEntry { at, height })
+            .collect()
+    }
+
+    #[test]
+    fn should_sum_up_postures_and_transitions() {
+        let entries = [
+            still(at(9, 0), at(10, 0), SITTING),
+            still(at(10, 0), at(10, 30), STANDING),
+            still(at(10, 30), at(11, 0), SITTING),
+            vec![HistoryEntry {
+                at: at(11, 0),
+                height: None,
+            }],
+        ]
+        .concat();
+
+        let stats = compute_stats(&entries, at(12, 0), THRESHOLD, &Utc);
+
+        let today = stats.days.last().unwrap();
+        assert_eq!(today.date, "2023-12-24");
+        assert_eq!(
+            today.stats,
+            PeriodStats {
+                sitting_minutes: 90,
+                standing_minutes: 30,
+                transitions: 2,
+                longest_sitting_minutes: 60,
+            }
+        );
+        assert_eq!(stats.days.len(), 7);
+        assert_eq!(stats.week, today.stats);
+    }
+
+    #[test]
+    fn should_not_count_time_the_app_was_not_running() {
+        let entries = vec![
+            HistoryEntry {
+                at: at(9, 0),
+                height: SITTING,
+            },
+            HistoryEntry {
+                at: at(15, 0),
+                height: STANDING,
+            },
+        ];
+
+        let stats = compute_stats(&entries, at(15, 5), THRESHOLD, &Utc);
+
+        assert_eq!(stats.week.sitting_minutes, 10);
+        assert_eq!(stats.week.standing_minutes, 5);
+        // There's a gap between the two, so nobody really switched
+        assert_eq!(stats.week.transitions, 0);
+    }
+
+    #[test]
+    fn should_split_time_at_midnight() {
+        let entries = [
+            still(at(23, 0), at(23, 55), SITTING),
+            still(at(23, 55) + 5 * 60, at(23, 55) + 35 * 60, SITTING),
+        ]
+        .concat();
+
+        let stats = compute_stats(&entries, at(23, 55) + 35 * 60, THRESHOLD, &Utc);
+
+        let days = &stats.days;
+        assert_eq!(days[days.len() - 2].stats.sitting_minutes, 60);
+        assert_eq!(days[days.len() - 1].date, "2023-12-25");
+        assert_eq!(days[days.len() - 1].stats.sitting_minutes, 30);
+        assert_eq!(days[days.len() - 2].stats.longest_sitting_minutes, 90);
+    }
+
+    #[test]
+    fn should_skip_entries_after_now() {
+        // Written before the clock was moved back
+        let entries = [
+            still(at(9, 0), at(9, 30), SITTING),
+            still(at(14, 0), at(14, 30), STANDING),
+        ]
+        .concat();
+
+        let stats = compute_stats(&entries, at(10, 0), THRESHOLD, &Utc);
+
+        assert_eq!(stats.week.sitting_minutes, 35);
+        assert_eq!(stats.week.standing_minutes, 0);
+    }
+
+    #[test]
+    fn should_skip_entries_with_unusable_dates() {
+        let entries = [
+            vec![HistoryEntry {
+                at: i64::MIN,
+                height: STANDING,
+            }],
+            still(at(9, 0), at(9, 30), SITTING),
+            // Too far in the future for a date - a garbled line which still parsed
+            still(i64::MAX - 10 * 60, i64::MAX, STANDING),
+        ]
+        .concat();
+
+        let stats = compute_stats(&entries, at(10, 0), THRESHOLD, &Utc);
+
+        assert_eq!(stats.week.sitting_minutes, 35);
+        assert_eq!(stats.week.standing_minutes, 0);
+    }
+}
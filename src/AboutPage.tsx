@@ -1,11 +1,26 @@
 import { useEffect, useState } from "react";
 import { relaunch } from "@tauri-apps/api/process";
+import { listen } from "@tauri-apps/api/event";
 import useSimpleAsync from "use-simple-async";
 import { enable, isEnabled, disable } from "tauri-plugin-autostart-api";
 import { HelpCircle } from "lucide-react";
-import { removeConfig } from "./rustUtils";
+import {
+  CONNECTION_CHANGED_EVENT,
+  ConnectionState,
+  errorMessage,
+  getConnectionState,
+  getHeightSettings,
+  HeightSettings,
+  HeightUnit,
+  heightUnitSymbol,
+  removeConfig,
+  setHeightCalibration,
+  setHeightLimits,
+} from "./rustUtils";
 import { Button } from "./generic/button";
 import { Checkbox } from "./generic/checkbox";
+import { Input } from "./generic/input";
+import { Label } from "./generic/label";
 import {
   Tooltip,
   TooltipContent,
@@ -14,12 +29,162 @@ import {
 } from "./generic/tooltip";
 import Href from "./generic/Href";
 
+const connectionStateLabel = (connectionState?: ConnectionState) => {
+  switch (connectionState?.state) {
+    case "connected":
+      return "Connected";
+    case "disconnected":
+      return "Disconnected";
+    case "reconnecting":
+      return `Reconnecting (attempt ${connectionState.attempt})`;
+    case "not_configured":
+      return "No desk set up";
+    default:
+      return "Unknown";
+  }
+};
+
+interface HeightSettingsProps {
+  heightSettings?: HeightSettings;
+  onSaved: (heightSettings: HeightSettings) => void;
+}
+
+/** Unit heights are shown in, and the offset between the height the desk reports and the real one */
+const HeightCalibration = ({ heightSettings, onSaved }: HeightSettingsProps) => {
+  const [unit, setUnit] = useState<HeightUnit>("cm");
+  const [offset, setOffset] = useState<string>("0");
+  const [message, setMessage] = useState<string>();
+
+  useEffect(() => {
+    if (heightSettings) {
+      setUnit(heightSettings.unit);
+      setOffset(heightSettings.offset.toFixed(1));
+    }
+  }, [heightSettings]);
+
+  return (
+    <div className="flex flex-col mb-3">
+      <Label className="mb-2" htmlFor="unitSelect">
+        Height unit
+      </Label>
+      <select
+        id="unitSelect"
+        className="mb-2 h-10 rounded-md border border-input bg-background px-3 text-sm"
+        value={unit}
+        onChange={(e) => setUnit(e.target.value as HeightUnit)}
+      >
+        <option value="cm">Centimetres</option>
+        <option value="inches">Inches</option>
+      </select>
+      <Label className="mb-2" htmlFor="offsetInput">
+        Calibration offset ({heightUnitSymbol(unit)}), added to the height the
+        desk reports
+      </Label>
+      <Input
+        id="offsetInput"
+        value={offset}
+        onChange={(e) => setOffset(e.target.value)}
+      />
+      <Button
+        className="mt-2"
+        onClick={async () => {
+          try {
+            onSaved(await setHeightCalibration(unit, Number(offset) || 0));
+            setMessage("Saved");
+          } catch (e) {
+            setMessage(errorMessage(e));
+          }
+        }}
+      >
+        Save
+      </Button>
+      {message && (
+        <p className="text-sm text-muted-foreground mt-1">{message}</p>
+      )}
+    </div>
+  );
+};
+
+/** Heights the desk never gets moved past, eg. because of a shelf below it */
+const HeightLimits = ({ heightSettings, onSaved }: HeightSettingsProps) => {
+  const [min, setMin] = useState<string>("");
+  const [max, setMax] = useState<string>("");
+  const [message, setMessage] = useState<string>();
+
+  useEffect(() => {
+    if (heightSettings) {
+      setMin(heightSettings.min.toFixed(1));
+      setMax(heightSettings.max.toFixed(1));
+    }
+  }, [heightSettings]);
+
+  if (!heightSettings) {
+    return null;
+  }
+  const unitSymbol = heightUnitSymbol(heightSettings.unit);
+
+  return (
+    <div className="flex flex-col mb-3">
+      <Label className="mb-2" htmlFor="minHeightInput">
+        Lowest height ({unitSymbol}) - the desk itself goes down to{" "}
+        {heightSettings.hardware_min.toFixed(1)}
+      </Label>
+      <Input
+        id="minHeightInput"
+        value={min}
+        onChange={(e) => setMin(e.target.value)}
+      />
+      <Label className="my-2" htmlFor="maxHeightInput">
+        Highest height ({unitSymbol}) - the desk itself goes up to{" "}
+        {heightSettings.hardware_max.toFixed(1)}
+      </Label>
+      <Input
+        id="maxHeightInput"
+        value={max}
+        onChange={(e) => setMax(e.target.value)}
+      />
+      <Button
+        className="mt-2"
+        onClick={async () => {
+          try {
+            onSaved(await setHeightLimits(Number(min), Number(max)));
+            setMessage("Saved");
+          } catch (e) {
+            setMessage(errorMessage(e));
+          }
+        }}
+      >
+        Save
+      </Button>
+      {message && (
+        <p className="text-sm text-muted-foreground mt-1">{message}</p>
+      )}
+    </div>
+  );
+};
+
 const AboutPage = () => {
   const [isAutostartEnabled, setAutostartEnabled] = useState<
     boolean | undefined
   >();
 
   const [upstreamAutostart] = useSimpleAsync(isEnabled);
+  const [connectionState, setConnectionState] = useState<ConnectionState>();
+  const [heightSettings, setHeightSettings] = useState<HeightSettings>();
+
+  useEffect(() => {
+    getHeightSettings().then(setHeightSettings);
+  }, []);
+
+  useEffect(() => {
+    getConnectionState().then(setConnectionState);
+    const unlisten = listen<ConnectionState>(CONNECTION_CHANGED_EVENT, (e) =>
+      setConnectionState(e.payload)
+    );
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
 
   useEffect(() => {
     if (isAutostartEnabled === undefined) {
@@ -37,6 +202,9 @@ const AboutPage = () => {
         >
           Options
         </h1>
+        <p className="text-sm text-muted-foreground mb-3">
+          Desk status: {connectionStateLabel(connectionState)}
+        </p>
         <div className="items-top flex space-x-2 mb-3">
           <Checkbox
             onCheckedChange={() => {
@@ -63,6 +231,14 @@ const AboutPage = () => {
             </p>
           </div>
         </div>
+        <HeightCalibration
+          heightSettings={heightSettings}
+          onSaved={setHeightSettings}
+        />
+        <HeightLimits
+          heightSettings={heightSettings}
+          onSaved={setHeightSettings}
+        />
 
         <div className="flex-col flex justify-center items-center">
           <h1
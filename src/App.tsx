@@ -6,7 +6,7 @@ import NewPositionPage from "./NewPositionPage";
 import IntroPage from "./IntroPage";
 import ManagePositionsPage from "./ManagePositionsPage";
 import { Button } from "./generic/button";
-import { connectToDesk, resetDesk } from "./rustUtils";
+import { connectToDesk, errorMessage, resetDesk } from "./rustUtils";
 import Spinner from "./generic/Spinner";
 
 // This error will only happen for users with a desk already set up. Intro Page errors are be handled in Intro Page.
@@ -40,7 +40,7 @@ const ReturningUserErrorPage = () => {
             try {
               await connectToDesk((window as any)?.stateWorkaround?.desk_name);
             } catch (e) {
-              setError(e as string);
+              setError(errorMessage(e));
             }
             setLoading(false);
           }}
@@ -1,10 +1,11 @@
 import { useState } from "react";
 import { Button } from "./generic/button";
 import Spinner from "./generic/Spinner";
-import { connectToDesk } from "./rustUtils";
+import { connectToDesk, errorMessage } from "./rustUtils";
 
 interface Props {
   deskName: string;
+  peripheralId?: string;
   onConnect?: () => void;
   onError: (err: string) => void;
   onLoadStart?: () => void;
@@ -15,6 +16,7 @@ interface Props {
 
 const DeskElement = ({
   deskName,
+  peripheralId,
   onConnect,
   onError,
   isConnected = false,
@@ -36,9 +38,9 @@ const DeskElement = ({
           setLoading(true);
           onLoadStart?.();
           try {
-            await connectToDesk(deskName);
+            await connectToDesk(deskName, peripheralId);
           } catch (e) {
-            onError(e as string);
+            onError(errorMessage(e));
           }
           setLoading(false);
           onLoadEnd?.();
@@ -1,7 +1,7 @@
 import { relaunch } from "@tauri-apps/api/process";
 import { Link } from "found";
-import { useState } from "react";
-import useSimpleAsync from "use-simple-async";
+import { listen } from "@tauri-apps/api/event";
+import { useCallback, useEffect, useState } from "react";
 import {
   TooltipProvider,
   Tooltip,
@@ -11,15 +11,74 @@ import {
 import DeskElement from "./DeskElement";
 import { Button } from "./generic/button";
 import Spinner from "./generic/Spinner";
-import { getAvailableDesks, removeConfig } from "./rustUtils";
+import {
+  AdapterError,
+  cancelDeskScan,
+  ConnectionDesk,
+  DESK_FOUND_EVENT,
+  errorMessage,
+  removeConfig,
+  SCAN_FINISHED_EVENT,
+  ScanFinished,
+  startDeskScan,
+} from "./rustUtils";
+
+// Desks show up as soon as they're found, so the scan can take its time
+const SCAN_TIMEOUT_SECS = 30;
+
+const useDeskScan = () => {
+  const [desks, setDesks] = useState<Array<ConnectionDesk>>([]);
+  const [scanning, setScanning] = useState(false);
+  const [error, setError] = useState<string>();
+  const [adapterErrors, setAdapterErrors] = useState<Array<AdapterError>>([]);
+
+  const startScan = useCallback(() => {
+    setDesks([]);
+    setError(undefined);
+    setAdapterErrors([]);
+    setScanning(true);
+    startDeskScan(SCAN_TIMEOUT_SECS).catch((e) => {
+      setError(errorMessage(e));
+      setScanning(false);
+    });
+  }, []);
+
+  useEffect(() => {
+    const unlistenFound = listen<ConnectionDesk>(DESK_FOUND_EVENT, (e) =>
+      setDesks((desks) => [
+        ...desks.filter(
+          (desk) => desk.peripheral_id !== e.payload.peripheral_id
+        ),
+        e.payload,
+      ])
+    );
+    const unlistenFinished = listen<ScanFinished>(SCAN_FINISHED_EVENT, (e) => {
+      setScanning(false);
+      setAdapterErrors(e.payload.adapter_errors);
+      if (e.payload.error) {
+        setError(e.payload.error);
+      }
+    });
+    // Only scan once we're listening, so that no desk is missed
+    Promise.all([unlistenFound, unlistenFinished]).then(startScan);
+    return () => {
+      cancelDeskScan();
+      unlistenFound.then((f) => f());
+      unlistenFinished.then((f) => f());
+    };
+  }, [startScan]);
+
+  return { desks, scanning, error, adapterErrors, startScan };
+};
 
 const IntroPage = () => {
-  const [data, { error, loading: devicesLoading, retry }] = useSimpleAsync(
-    getAvailableDesks,
-    {
-      useLayout: true,
-    }
-  );
+  const {
+    desks: data,
+    scanning: devicesLoading,
+    error,
+    adapterErrors,
+    startScan,
+  } = useDeskScan();
   const [connectingLoading, setConnectingLoading] = useState(false);
   const [isConnected, setIsConnected] = useState(false);
   const [connectedNewDesk, setConnectedNewDesk] = useState<string>();
@@ -49,9 +108,12 @@ const IntroPage = () => {
     );
   }
 
-  const dataToDisplay = showAll
-    ? data
-    : data?.filter((e) => e.name.includes("Desk"));
+  // Nearby desks first - RSSI doesn't go below -127
+  const dataToDisplay = (
+    showAll ? data : data.filter((e) => e.name.includes("Desk"))
+  )
+    .slice()
+    .sort((a, b) => (b.rssi ?? -128) - (a.rssi ?? -128));
 
   return (
     <div className="w-full h-full flex flex-col justify-center items-center">
@@ -70,20 +132,25 @@ const IntroPage = () => {
         <>
           <p>No saved desk found. Connect to one of desks listed below:</p>
           <div className="w-64 overflow-x-auto p-2 h-64">
-            {devicesLoading ? (
+            {devicesLoading && !dataToDisplay.length ? (
               <div className="flex items-center justify-center flex-col h-full">
                 <Spinner size="lg" />
                 Searching for bluetooth devices...
               </div>
             ) : (
-              dataToDisplay?.map((e, i) => (
+              dataToDisplay.map((e) => (
                 <DeskElement
-                  key={i}
+                  key={e.peripheral_id}
                   disabled={!!connectingLoading}
-                  onLoadStart={() => setConnectingLoading(true)}
+                  onLoadStart={() => {
+                    // Some adapters can't connect while scanning
+                    cancelDeskScan();
+                    setConnectingLoading(true);
+                  }}
                   onLoadEnd={() => setConnectingLoading(false)}
                   onError={setDeskError}
                   deskName={e.name}
+                  peripheralId={e.peripheral_id}
                   onConnect={() => {
                     setIsConnected(true);
                     setConnectedNewDesk(e.name);
@@ -93,6 +160,12 @@ const IntroPage = () => {
               ))
             )}
           </div>
+          {adapterErrors.length > 0 && (
+            <p>
+              Some bluetooth adapters could not scan:{" "}
+              {adapterErrors.map((e) => `${e.adapter} (${e.error})`).join(", ")}
+            </p>
+          )}
           If your desk has a different name from "Desk XXXX", click the button
           below to expand the list
           <div>
@@ -100,7 +173,7 @@ const IntroPage = () => {
               className="mr-1"
               disabled={!!connectedNewDesk || !!devicesLoading}
               onClick={() => {
-                retry();
+                startScan();
               }}
             >
               Refresh
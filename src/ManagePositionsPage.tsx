@@ -1,12 +1,42 @@
+import { useEffect } from "react";
+import { listen } from "@tauri-apps/api/event";
 import useSimpleAsync from "use-simple-async";
 import { appWindow } from "@tauri-apps/api/window";
 import { Link } from "found";
 import { Button } from "./generic/button";
 import removeIcon from "./assets/cross.svg";
-import { getPositions, removePosition } from "./rustUtils";
+import {
+  CONFIG_CHANGED_EVENT,
+  getActivePositions,
+  getHeightSettings,
+  getPositions,
+  heightUnitSymbol,
+  removePosition,
+} from "./rustUtils";
 
 const ManagePositionsPage = () => {
   const [data, { retry }] = useSimpleAsync(getPositions);
+  const [heightSettings, { retry: retryHeights }] =
+    useSimpleAsync(getHeightSettings);
+
+  // Positions might get added or removed from another window
+  useEffect(() => {
+    const unlisten = listen(CONFIG_CHANGED_EVENT, () => {
+      retry();
+      retryHeights();
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, [retry, retryHeights]);
+
+  // Heights are converted to the unit the user picked on the rust side
+  const heightLabel = (name: string) => {
+    const position = heightSettings?.positions.find((pos) => pos.name === name);
+    return position && heightSettings
+      ? `${position.height.toFixed(1)} ${heightUnitSymbol(heightSettings.unit)}`
+      : "";
+  };
 
   console.log(data);
   return (
@@ -26,8 +56,8 @@ const ManagePositionsPage = () => {
             <th className="sticky top-0 bg-slate-800">Shortcut</th>
             <th className="sticky top-0 bg-slate-800">Actions</th>
           </thead>
-          {data?.saved_positions
-            ? data?.saved_positions.map(({ name, value, shortcut }) => (
+          {data
+            ? getActivePositions(data).map(({ name, shortcut }) => (
                 <tbody className="contents" key={name}>
                   {/**
                    * TODO: Add a tooltip or some shit
@@ -35,7 +65,7 @@ const ManagePositionsPage = () => {
                   <td className="h-8 text-ellipsis overflow-hidden inline-block whitespace-nowrap max-w-xs">
                     {name}
                   </td>
-                  <td className="h-8">{value}</td>
+                  <td className="h-8">{heightLabel(name)}</td>
                   <td className="h-8">{shortcut}</td>
                   <td className="h-8 flex flex-row-reverse">
                     <img
@@ -2,8 +2,13 @@ import React, { useCallback, useEffect, useState } from "react";
 import { appWindow } from "@tauri-apps/api/window";
 import { Button } from "./generic/button";
 import { Input } from "./generic/input";
-import { MAX_HEIGHT, MIN_HEIGHT } from "./utils";
-import { createNewElem } from "./rustUtils";
+import useSimpleAsync from "use-simple-async";
+import {
+  createNewElem,
+  errorMessage,
+  getHeightSettings,
+  heightUnitSymbol,
+} from "./rustUtils";
 import { Label } from "./generic/label";
 
 // Maps browser keys into accelerator keys
@@ -50,14 +55,14 @@ enum KeystoreStatusTexts {
 
 enum ErrorCodes {
   no_name = "Name cannot be empty",
-  wrong_value = "Value has to be between 6200 and 12700", // MIN_HEIGHT and MAX_HEIGHT
   value_string = "Value has to be a number",
   duplicate = "A position with that name already exists",
 }
 
 const NewPositionPage = () => {
   const [name, setName] = useState<string>("");
-  const [value, setValue] = useState<string>("7200");
+  const [value, setValue] = useState<string>("");
+  const [heightSettings] = useSimpleAsync(getHeightSettings);
   const [error, setError] = useState<string | undefined>();
   const [shortcutValue, setShortcutValue] = useState<string>("");
   console.log(shortcutValue);
@@ -158,7 +163,9 @@ const NewPositionPage = () => {
           </Label>
           <Input value={name} id="nameInput" onChange={handleChangeName} />
           <Label className="mt-4 mb-2" htmlFor="valueInput">
-            Position height (between {MIN_HEIGHT} and {MAX_HEIGHT})
+            Position height
+            {heightSettings &&
+              ` (between ${heightSettings.min.toFixed(1)} and ${heightSettings.max.toFixed(1)} ${heightUnitSymbol(heightSettings.unit)})`}
           </Label>
           <Input value={value} id="valueInput" onChange={handleChangeValue} />{" "}
           <Label className="mt-4 mb-2" htmlFor="shortcutInput">
@@ -195,8 +202,6 @@ const NewPositionPage = () => {
               locErr = ErrorCodes.no_name;
             } else if (isNaN(valAsNum) || !valAsNum) {
               locErr = ErrorCodes.value_string;
-            } else if (valAsNum < MIN_HEIGHT || valAsNum > MAX_HEIGHT) {
-              locErr = ErrorCodes.wrong_value;
             } else {
               locErr = undefined;
               setError(undefined);
@@ -205,8 +210,14 @@ const NewPositionPage = () => {
             if (locErr) {
               setError(locErr);
             } else {
-              // try to create an elem
-              let resp = await createNewElem(name, value, shortcutValue);
+              // try to create an elem - the height gets checked on the rust side
+              let resp;
+              try {
+                resp = await createNewElem(name, value, shortcutValue);
+              } catch (e) {
+                setError(errorMessage(e));
+                return;
+              }
 
               if (resp === "duplicate") {
                 setError(ErrorCodes.duplicate);
@@ -4,12 +4,35 @@
 
 import { invoke } from "@tauri-apps/api";
 
-export const connectToDesk = async (name: string) => {
-  return await invoke("connect_to_desk_by_name", { name });
+/** What failing commands reject with */
+export interface AppError {
+  kind:
+    | "desk_not_connected"
+    | "bluetooth"
+    | "config"
+    | "presets"
+    | "not_found"
+    | "invalid_input";
+  message: string;
+}
+
+/** Readable message of anything a command rejected with */
+export const errorMessage = (error: unknown): string => {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    return String((error as AppError).message);
+  }
+  return String(error);
+};
+
+export const connectToDesk = async (name: string, peripheralId?: string) => {
+  return await invoke("connect_to_desk_by_name", { name, peripheralId });
 };
 
 export interface ConnectionDesk {
   name: string;
+  peripheral_id: string;
+  /** Signal strength, higher is closer */
+  rssi: number | null;
   status: "new" | "saved";
 }
 
@@ -19,11 +42,76 @@ export const getAvailableDesks = async () => {
   )) as Array<ConnectionDesk>;
 };
 
+/** Sent with every desk found by a scan session */
+export const DESK_FOUND_EVENT = "desk-found";
+/** Sent once a scan session ends - when it times out, gets cancelled or fails */
+export const SCAN_FINISHED_EVENT = "desk-scan-finished";
+
+export interface AdapterError {
+  adapter: string;
+  error: string;
+}
+
+export interface ScanFinished {
+  error: string | null;
+  /** Adapters which could not scan - the rest kept going */
+  adapter_errors: Array<AdapterError>;
+}
+
+/** Starts a scan session, replacing the one in progress */
+export const startDeskScan = async (timeoutSecs?: number) => {
+  return await invoke("start_desk_scan", { timeoutSecs });
+};
+
+export const cancelDeskScan = async () => {
+  return await invoke("cancel_desk_scan");
+};
+
+export interface Position {
+  name: string;
+  value: number;
+  shortcut?: string;
+}
+
+export interface DeskProfile {
+  name: string;
+  saved_positions: Array<Position>;
+  /** Tenth mm added to the height the desk reports */
+  height_offset: number;
+  /** Bluetooth peripheral id, missing until the desk is connected */
+  peripheral_id?: string;
+  /** Bluetooth address, missing until the desk is connected and on macOS */
+  address?: string;
+}
+
+export interface DeskGroup {
+  name: string;
+  desks: Array<string>;
+  saved_positions: Array<Position>;
+}
+
 export interface Config {
+  version: number;
+  /** Desk in use */
   local_name: string;
-  saved_positions: Array<{ name: string; value: number; shortcut?: string }>;
+  desks: Array<DeskProfile>;
+  groups: Array<DeskGroup>;
+  stop_shortcut?: string;
+  /** Unit heights are shown and entered in */
+  height_unit?: HeightUnit;
 }
 
+/** Positions of the desk in use */
+export const getActivePositions = (config: Config): Array<Position> => {
+  const localName = config.local_name ?? "";
+  return (
+    config.desks.find((desk) => desk.name === localName)?.saved_positions ?? []
+  );
+};
+
+/** Emitted with the new config every time it changes */
+export const CONFIG_CHANGED_EVENT = "config-changed";
+
 export const getPositions = async (): Promise<Config> => {
   return await invoke("get_config");
 };
@@ -32,6 +120,7 @@ export const removePosition = async (positionName: string): Promise<Config> => {
   return await invoke("remove_position", { posName: positionName });
 };
 
+/** `value` is in the unit the user picked */
 export const createNewElem = async (
   name: string,
   value: string | number,
@@ -45,14 +134,123 @@ export const createNewElem = async (
   });
 };
 
+export const setStopShortcut = async (shortcut?: string): Promise<Config> => {
+  return await invoke("set_stop_shortcut", {
+    shortcut: shortcut !== "" ? shortcut : undefined,
+  });
+};
+
+export type HeightUnit = "cm" | "inches";
+
+/** Heights of the desk in use, converted to the unit the user picked */
+export interface HeightSettings {
+  unit: HeightUnit;
+  /** Calibration offset of the desk */
+  offset: number;
+  /** Height limits of the desk */
+  min: number;
+  max: number;
+  /** Range the desk can physically move in */
+  hardware_min: number;
+  hardware_max: number;
+  positions: Array<{ name: string; height: number }>;
+}
+
+export const getHeightSettings = async (): Promise<HeightSettings> => {
+  return await invoke("get_height_settings");
+};
+
+/** `offset` is in the provided unit */
+export const setHeightCalibration = async (
+  unit: HeightUnit,
+  offset: number
+): Promise<HeightSettings> => {
+  return await invoke("set_height_calibration", { unit, offset });
+};
+
+/** Heights are in the unit from the height settings */
+export const setHeightLimits = async (
+  min: number,
+  max: number
+): Promise<HeightSettings> => {
+  return await invoke("set_height_limits", { min, max });
+};
+
+export const heightUnitSymbol = (unit: HeightUnit) =>
+  unit === "inches" ? "in" : "cm";
+
 export const removeConfig = async () => {
   return await invoke("remove_config");
 };
 
+/** How a single desk of a group did - `error` is missing when the desk arrived */
+export interface DeskMoveResult {
+  desk: string;
+  error?: string;
+}
+
+export const moveGroup = async (
+  group: string,
+  preset: string
+): Promise<Array<DeskMoveResult>> => {
+  return await invoke("move_group", { group, preset });
+};
+
 export const resetDesk = async () => {
   return await invoke("reset_desk");
 };
 
+export type ConnectionState =
+  | { state: "not_configured" }
+  | { state: "connected" }
+  | { state: "disconnected" }
+  | { state: "reconnecting"; attempt: number };
+
+/** Emitted every time the connection state changes */
+export const CONNECTION_CHANGED_EVENT = "desk-connection-changed";
+
+export const getConnectionState = async (): Promise<ConnectionState> => {
+  return await invoke("get_connection_state");
+};
+
+export interface PeriodStats {
+  sitting_minutes: number;
+  standing_minutes: number;
+  transitions: number;
+  longest_sitting_minutes: number;
+}
+
+export interface UsageStats {
+  /** Last 7 days, oldest first */
+  days: Array<PeriodStats & { date: string }>;
+  week: PeriodStats;
+}
+
+export const getUsageStats = async (): Promise<UsageStats> => {
+  return await invoke("get_usage_stats");
+};
+
+export type ImportMode = "merge" | "replace" | "skip_duplicates";
+
+export interface ImportSummary {
+  added: number;
+  updated: number;
+  skipped: number;
+  removed: number;
+}
+
+/** `.toml` paths are written as TOML, anything else as JSON */
+export const exportPresets = async (path: string) => {
+  return await invoke("export_presets_to_path", { path });
+};
+
+export const importPresets = async (
+  path: string,
+  mode: ImportMode
+): Promise<ImportSummary> => {
+  return await invoke("import_presets_from_path", { path, mode });
+};
+
 export const hasCustomDecorations = async () => {
   return await invoke("has_custom_decorations");
 };
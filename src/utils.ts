@@ -1,2 +0,0 @@
-export const MIN_HEIGHT = 6200;
-export const MAX_HEIGHT = 12700;